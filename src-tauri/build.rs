@@ -1,3 +1,37 @@
 fn main() {
+    // tauri_buildがrerun-if-changedを出すので、ソースの変更でもビルド時刻を更新する
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    // get_app_infoで返すビルド時刻 unix time(ms)
+    // 再現可能なビルドのためにSOURCE_DATE_EPOCH(秒)があればそちらを使う
+    let build_timestamp = match std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u128>().ok())
+    {
+        Some(epoch) => epoch * 1000,
+        None => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default(),
+    };
+    println!("cargo:rustc-env=BUILD_TIMESTAMP={build_timestamp}");
+
+    // gitがない環境（ソースのアーカイブなど）ではunknownにする
+    let git_hash = std::process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|hash| !hash.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=GIT_COMMIT_HASH={git_hash}");
+    // コミットでハッシュが変わるので、HEADと指しているブランチを見る
+    if let Ok(head) = std::fs::read_to_string("../.git/HEAD") {
+        println!("cargo:rerun-if-changed=../.git/HEAD");
+        if let Some(reference) = head.trim().strip_prefix("ref: ") {
+            println!("cargo:rerun-if-changed=../.git/{reference}");
+        }
+    }
     tauri_build::build()
 }
@@ -0,0 +1,53 @@
+use keyring::Entry;
+use log::warn;
+
+use crate::preferences::PasswordStorage;
+
+const KEYRING_SERVICE: &str = "rl_replay";
+
+// 接続先ごとに別のエントリにする
+fn entry(host: &str, port: u16) -> keyring::Result<Entry> {
+    Entry::new(KEYRING_SERVICE, &format!("obs://{host}:{port}"))
+}
+
+// OSのキーリングに保存する。使えない環境では平文で持つしかない
+pub fn store_password(host: &str, port: u16, password: Option<&str>) -> PasswordStorage {
+    let Some(password) = password else {
+        delete_password(host, port);
+        return PasswordStorage::None;
+    };
+    match entry(host, port).and_then(|entry| entry.set_password(password)) {
+        Ok(()) => PasswordStorage::Keyring,
+        Err(e) => {
+            warn!(
+                "OS keyring is unavailable, storing OBS password in plaintext: {}",
+                e
+            );
+            PasswordStorage::Plaintext {
+                password: password.to_string(),
+            }
+        }
+    }
+}
+
+pub fn load_password(
+    host: &str,
+    port: u16,
+    storage: &PasswordStorage,
+) -> Result<Option<String>, String> {
+    match storage {
+        PasswordStorage::None => Ok(None),
+        PasswordStorage::Plaintext { password } => Ok(Some(password.clone())),
+        PasswordStorage::Keyring => entry(host, port)
+            .and_then(|entry| entry.get_password())
+            .map(Some)
+            .map_err(|e| format!("Failed to read OBS password from keyring: {e}")),
+    }
+}
+
+pub fn delete_password(host: &str, port: u16) {
+    match entry(host, port).and_then(|entry| entry.delete_credential()) {
+        Ok(()) | Err(keyring::Error::NoEntry) => {}
+        Err(e) => warn!("Failed to delete OBS password from keyring: {}", e),
+    }
+}
@@ -0,0 +1,65 @@
+use serde::Deserialize;
+
+use crate::recording::RecordedMessage;
+
+// run_demoで流す試合の台本
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub enum DemoScenario {
+    // ゴール1回だけ
+    SingleGoal,
+    // 開始からゴール・セーブを挟んで終了まで
+    FullMatch,
+}
+
+impl DemoScenario {
+    // (開始からの時間ms, コマンド名)
+    fn script(self) -> &'static [(u64, &'static str)] {
+        match self {
+            DemoScenario::SingleGoal => &[(0, "start"), (3_000, "scored"), (15_000, "end")],
+            DemoScenario::FullMatch => &[
+                (0, "start"),
+                (8_000, "scored"),
+                (20_000, "epicSave"),
+                (23_000, "scored"),
+                (40_000, "epicSave"),
+                (55_000, "scored"),
+                (70_000, "end"),
+            ],
+        }
+    }
+
+    // 再送と見なされないようにeventIdを振り、デモのクリップと分かるようにタグを付ける
+    pub fn messages(self) -> Vec<RecordedMessage> {
+        self.script()
+            .iter()
+            .enumerate()
+            .map(|(i, (offset_ms, cmd))| RecordedMessage {
+                offset_ms: *offset_ms,
+                data: format!(r#"{{"cmd":"{cmd}","data":{{"eventId":"demo-{i}","demo":true}}}}"#),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mugi_schema::{MugiCmd, parse_cmd, parse_event_id};
+
+    #[test]
+    fn test_scenarios_parse() {
+        for scenario in [DemoScenario::SingleGoal, DemoScenario::FullMatch] {
+            let messages = scenario.messages();
+            assert!(messages.windows(2).all(|w| w[0].offset_ms < w[1].offset_ms));
+            for message in &messages {
+                assert!(parse_cmd(&message.data).is_ok(), "{}", message.data);
+                assert!(parse_event_id(&message.data).is_some());
+            }
+            assert_eq!(parse_cmd(&messages[0].data).unwrap(), MugiCmd::Start);
+            assert_eq!(
+                parse_cmd(&messages.last().unwrap().data).unwrap(),
+                MugiCmd::End
+            );
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::{Arc, Mutex};
+
+use log::{error, warn};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Runtime, Wry};
+use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::error::TrySendError;
+
+// フロントエンドに送るイベントのペイロード（型ごとにイベント名を決める）
+pub trait AppEvent: Serialize + Clone {
+    const NAME: &'static str;
+}
+
+// 各モジュールからフロントエンドへイベントを送る窓口
+// ランタイムを差し替えれば実際のアプリなしでも使える（tauri::test）
+#[derive(Clone)]
+pub struct EventSender<R: Runtime = Wry> {
+    app_handle: AppHandle<R>,
+}
+
+impl<R: Runtime> EventSender<R> {
+    pub fn new(app_handle: AppHandle<R>) -> Self {
+        Self { app_handle }
+    }
+
+    // 送れなくても呼び出し元の処理は止めない
+    pub fn emit<E: AppEvent>(&self, payload: E) {
+        if let Err(e) = self.app_handle.emit(E::NAME, payload) {
+            error!("Failed to emit {} event: {}", E::NAME, e);
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct ChannelOverflowPayload {
+    channel: &'static str,
+    // これまでに捨てた合計
+    dropped: u64,
+}
+
+impl AppEvent for ChannelOverflowPayload {
+    const NAME: &'static str = "channel-overflow";
+}
+
+// 満杯のチャンネルに送れなかったメッセージを数えて知らせる
+// 受信側が詰まっている間は送信側を止めずに捨てる
+pub struct OverflowReporter {
+    channel: &'static str,
+    dropped: Arc<Mutex<u64>>,
+    events: EventSender,
+    // 溢れ始めたときだけ知らせる（捨てるたびに送るとフロントエンドも溢れる）
+    overflowing: bool,
+}
+
+impl OverflowReporter {
+    pub fn new(channel: &'static str, dropped: Arc<Mutex<u64>>, events: EventSender) -> Self {
+        Self {
+            channel,
+            dropped,
+            events,
+            overflowing: false,
+        }
+    }
+
+    // 受信側が閉じていたらfalse
+    pub fn send<T>(&mut self, tx: &Sender<T>, value: T) -> bool {
+        match tx.try_send(value) {
+            Ok(()) => {
+                self.overflowing = false;
+                true
+            }
+            Err(TrySendError::Full(_)) => {
+                let dropped = {
+                    let mut dropped = self.dropped.lock().unwrap();
+                    *dropped += 1;
+                    *dropped
+                };
+                if !self.overflowing {
+                    self.overflowing = true;
+                    warn!("{} channel is full, dropping messages", self.channel);
+                    self.events.emit(ChannelOverflowPayload {
+                        channel: self.channel,
+                        dropped,
+                    });
+                }
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
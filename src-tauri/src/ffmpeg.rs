@@ -0,0 +1,205 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+
+fn command(program: &str) -> Command {
+    let mut cmd = Command::new(program);
+    // リリースビルドでコンソールウィンドウが出ないようにする
+    #[cfg(windows)]
+    cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+    cmd
+}
+
+// ffprobeを実行できるか（同梱していないので入っていないこともある）
+pub async fn is_ffprobe_available() -> bool {
+    command("ffprobe")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .is_ok_and(|status| status.success())
+}
+
+// 動画の長さ(秒)
+pub async fn probe_duration(path: &Path) -> Result<f64, String> {
+    let output = command("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ])
+        .arg(path)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffprobe: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<f64>()
+        .map_err(|e| format!("Failed to parse duration: {e}"))
+}
+
+// 映像の先頭1フレームが読めるか確認する
+pub async fn probe_first_frame(path: &Path) -> Result<(), String> {
+    let size = std::fs::metadata(path)
+        .map_err(|e| format!("Failed to read metadata: {e}"))?
+        .len();
+    if size == 0 {
+        return Err("ファイルが空です".to_string());
+    }
+    let output = command("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "v:0",
+            "-read_intervals",
+            "%+#1",
+            "-count_frames",
+            "-show_entries",
+            "stream=nb_read_frames",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ])
+        .arg(path)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffprobe: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let frames = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse::<u64>()
+        .unwrap_or(0);
+    if frames == 0 {
+        return Err("映像フレームを読み込めません".to_string());
+    }
+    Ok(())
+}
+
+// clip.mkv -> clip_trim.mkv
+pub fn trimmed_path(input: &Path) -> PathBuf {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match input.extension() {
+        Some(ext) => format!("{}_trim.{}", stem, ext.to_string_lossy()),
+        None => format!("{}_trim", stem),
+    };
+    input.with_file_name(file_name)
+}
+
+// clip.mkv -> clip_transcoded.mp4
+pub fn transcoded_path(input: &Path, extension: &str) -> PathBuf {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    input.with_file_name(format!("{}_transcoded.{}", stem, extension))
+}
+
+// webmにはaacを入れられないのでopusにする
+fn audio_codec(output: &Path) -> &'static str {
+    match output.extension() {
+        Some(extension) if extension.eq_ignore_ascii_case("webm") => "libopus",
+        _ => "aac",
+    }
+}
+
+// 映像をvideo_codec・ビットレート指定で再エンコードする（音声は形式に合わせてaacかopus）
+pub async fn transcode(
+    input: &Path,
+    output: &Path,
+    video_codec: &str,
+    video_bitrate_kbps: u32,
+) -> Result<(), String> {
+    let result = command("ffmpeg")
+        .args(["-y", "-v", "error", "-i"])
+        .arg(input)
+        .args(["-c:v", video_codec, "-b:v"])
+        .arg(format!("{video_bitrate_kbps}k"))
+        .args(["-c:a", audio_codec(output)])
+        .arg(output)
+        // キャンセルでタスクごと止めたときにffmpegも止める
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run ffmpeg: {e}"))?;
+    if !result.status.success() {
+        return Err(format!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+// start_sec~end_secを切り出す
+// reencode=falseならストリームコピー
+// on_progressには0.0~1.0の進捗が渡される
+pub async fn trim(
+    input: &Path,
+    output: &Path,
+    start_sec: f64,
+    end_sec: f64,
+    reencode: bool,
+    on_progress: impl Fn(f64),
+) -> Result<(), String> {
+    let length = end_sec - start_sec;
+    let mut cmd = command("ffmpeg");
+    cmd.args(["-y", "-v", "error", "-nostats", "-progress", "pipe:1"])
+        .arg("-ss")
+        .arg(start_sec.to_string())
+        .arg("-i")
+        .arg(input)
+        .arg("-t")
+        .arg(length.to_string());
+    if reencode {
+        cmd.args(["-c:v", "libx264", "-c:a", "aac"]);
+    } else {
+        cmd.args(["-c", "copy"]);
+    }
+    let mut child = cmd
+        .arg(output)
+        // キャンセルでタスクごと止めたときにffmpegも止める
+        .kill_on_drop(true)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run ffmpeg: {e}"))?;
+
+    // -progressの出力からout_time_us(マイクロ秒)を拾う
+    if let Some(stdout) = child.stdout.take() {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Some(us) = line
+                .strip_prefix("out_time_us=")
+                .and_then(|v| v.parse::<f64>().ok())
+            {
+                on_progress((us / 1_000_000.0 / length).clamp(0.0, 1.0));
+            }
+        }
+    }
+
+    let result = child
+        .wait_with_output()
+        .await
+        .map_err(|e| format!("Failed to wait ffmpeg: {e}"))?;
+    if !result.status.success() {
+        return Err(format!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        ));
+    }
+    Ok(())
+}
@@ -1,27 +1,432 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
+mod credentials;
+mod demo;
+mod events;
+mod ffmpeg;
+mod mock_obs;
 mod mugi_schema;
 mod obs;
+mod preferences;
+mod recording;
+mod sequence;
+mod session;
+mod sidecar;
 mod udp;
+mod ui_preferences;
 mod vlc_manager;
+mod webhook;
 
-use log::{debug, error, info};
+use demo::DemoScenario;
+use events::{AppEvent, EventSender, OverflowReporter};
+use log::{debug, error, info, warn};
+use mock_obs::MockObs;
 use mugi_schema::MugiCmd;
+use obs::{
+    AudioState, HighlightFit, PlaybackAudioMode, PlaybackDeadline, PlaybackOptions,
+    SceneTransition, SourceTransform, VlcPlaylist,
+};
+use preferences::{PasswordStorage, StoredConnection};
+use recording::{SharedRecording, TriggerRecording};
+use sequence::{SequenceMatcher, TriggerSequence};
+use serde::{Deserialize, Serialize};
+use session::{
+    ClipKind, SessionClip, SessionCounter, SessionManifest, SessionMarker, SessionStats,
+};
+use sidecar::ClipSidecar;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::{Arc, Mutex, RwLock};
-use tauri::AppHandle;
+use tauri::{AppHandle, Emitter, Manager};
 use tauri_plugin_log::{Target, TargetKind};
 use tauri_plugin_updater::UpdaterExt;
 use tokio::sync::mpsc::{self};
-use udp::bind_socket;
-use vlc_manager::VlcManager;
+use tokio::sync::oneshot;
+use tokio::task::{AbortHandle, JoinHandle};
+use udp::{DEFAULT_BIND_ADDR, UdpFraming, UdpMessage, supervise_socket};
+use ui_preferences::UiPreferences;
+use vlc_manager::{
+    ClipList, FormatWarning, NamedPlaylists, NextReplay, ReplayRedirect, SUPPORTED_EXTENSIONS,
+    VlcManager,
+};
+use webhook::WebhookPayload;
 
 // 複雑な型を簡素化するためのtype alias
-type ObsConnectionInfo = Arc<Mutex<Option<(String, u16, Option<String>)>>>;
+// (host, port, password, secure)
+type ObsConnectionInfo = Arc<Mutex<Option<(String, u16, Option<String>, bool)>>>;
 
 // グローバル状態管理用の構造体
+#[derive(Clone)]
 struct AppState {
     obs_connection_info: ObsConnectionInfo,
     is_system_running: Arc<Mutex<bool>>,
+    // connect_obsの途中（二重クリックで同時に呼ばれても片方だけ進める）
+    is_connecting: Arc<Mutex<bool>>,
+    is_capture_paused: Arc<Mutex<bool>>,
+    // falseならUDPコマンドを処理せずに捨てる（OBSのリプレイバッファはそのまま）
+    is_udp_processing: Arc<Mutex<bool>>,
+    // VLCソースを用意できず、ハイライト再生が使えない
+    is_playback_unavailable: Arc<Mutex<bool>>,
+    // capture_clipの同時実行を防ぐ
+    is_capturing_clip: Arc<Mutex<bool>>,
     sleep_duration_sec: Arc<RwLock<u64>>,
+    // 起動直後はOBSの準備ができていないことがあるので、起動からこの秒数までは接続を待つ
+    startup_delay_sec: Arc<RwLock<u64>>,
+    launched_at: std::time::Instant,
+    // 遅延中の保存タスク（キャンセル用）
+    pending_save: Arc<Mutex<Option<AbortHandle>>>,
+    system_task: Arc<Mutex<Option<JoinHandle<()>>>>,
+    webhook_url: Arc<RwLock<Option<String>>>,
+    // 保存のトリガーになるコマンド
+    trigger_cmds: Arc<RwLock<HashSet<MugiCmd>>>,
+    // 並びが揃ったときに保存する（Noneで無効）
+    trigger_sequence: Arc<RwLock<Option<TriggerSequence>>>,
+    playback_audio_mode: Arc<RwLock<PlaybackAudioMode>>,
+    clips: ClipList,
+    trigger_latencies: Arc<Mutex<VecDeque<TriggerLatency>>>,
+    // 受け付けたトリガー（古い順）
+    trigger_history: Arc<Mutex<VecDeque<TriggerHistoryEntry>>>,
+    // レビューで付けた山場の位置から学習した遅延
+    peak_offset: Arc<Mutex<PeakOffsetEma>>,
+    capture_mode: Arc<RwLock<CaptureMode>>,
+    next_event_timeout_sec: Arc<RwLock<u64>>,
+    // EpicSaveの後この秒数以内にScoredが来たら1つのクリップにまとめる（0で無効）
+    combo_window_sec: Arc<RwLock<u64>>,
+    // ハートビートがこの秒数途切れたらmatchEndedで保存する（0で無効）
+    heartbeat_timeout_sec: Arc<RwLock<u64>>,
+    // ハイライト再生中に切り替えるシーン
+    highlights_scene: Arc<RwLock<Option<String>>>,
+    highlight_fit: Arc<RwLock<HighlightFit>>,
+    // VLCソースをPiPで再生する位置・サイズ（Noneならhighlight_fitに従う）
+    playback_transform: Arc<RwLock<Option<SourceTransform>>>,
+    // VLCソースのキャッシュ時間(ms)
+    playback_buffering_ms: Arc<RwLock<u64>>,
+    playback_loop: Arc<RwLock<bool>>,
+    // 通常のVLCソースで最後に再生を始めたプレイリスト（追加で伸びる）
+    playback_playlist: Arc<Mutex<Vec<PathBuf>>>,
+    // 再生中のハイライトを終わったものとして扱う時刻
+    playback_deadline: PlaybackDeadline,
+    // 再生開始時に最初のフレームで止めておく時間(ms)（0で止めない）
+    playback_freeze_intro_ms: Arc<RwLock<u64>>,
+    // 再生用ウィンドウでこれから再生するクリップ
+    window_queue: Arc<Mutex<VecDeque<PathBuf>>>,
+    // 再生用ウィンドウに送ったクリップ（これだけをclipプロトコルで読める）
+    window_clip: Arc<Mutex<Option<PathBuf>>>,
+    // 再生終了後にVLCソースを非表示に戻す
+    hide_when_idle: Arc<RwLock<bool>>,
+    // NoneならOBSで選択中のトランジションを使う
+    scene_transition: Arc<RwLock<Option<SceneTransition>>>,
+    // このアプリが保存したクリップ（古い順）
+    created_clips: Arc<Mutex<VecDeque<PathBuf>>>,
+    max_clips: Arc<RwLock<Option<usize>>>,
+    session: Arc<Mutex<Option<SessionManifest>>>,
+    session_counter: Arc<Mutex<Option<SessionCounter>>>,
+    // 動作中のシステムが使うOBS接続（再接続で差し替える）
+    obs: Arc<Mutex<Option<Arc<obs::Obs>>>>,
+    // リプレイの保存を1つずつにする（benchmark_saveと本物のトリガーを重ねない）
+    save_lock: Arc<tokio::sync::Mutex<()>>,
+    // 変換を1つずつにする（続けて保存したときにffmpegを並べて走らせない）
+    transcode_lock: Arc<tokio::sync::Mutex<()>>,
+    // トランジション中なら終わるまで保存を待つ
+    transition_aware: Arc<RwLock<bool>>,
+    // 接続時に切り替えるOBSのプロファイルとシーンコレクション（Noneならそのまま）
+    obs_profile: Arc<RwLock<Option<String>>>,
+    obs_scene_collection: Arc<RwLock<Option<String>>>,
+    // 保存したクリップをすぐにVLCソースで再生する
+    instant_replay: Arc<RwLock<bool>>,
+    obs_event_listener: Arc<Mutex<Option<AbortOnDrop>>>,
+    // OBSイベントリスナーからVlcManagerへのリプレイパス送信用
+    replay_path_tx: Arc<Mutex<Option<mpsc::Sender<PathBuf>>>>,
+    // OBS切断中に受信したトリガー
+    pending_triggers: Arc<Mutex<VecDeque<Trigger>>>,
+    pending_trigger_policy: Arc<RwLock<PendingTriggerPolicy>>,
+    // 0なら空き容量を確認しない
+    min_free_space_mb: Arc<RwLock<u64>>,
+    disk_full_policy: Arc<RwLock<DiskFullPolicy>>,
+    // trueならOBSが配信・録画中のときだけ保存する
+    require_active_output: Arc<RwLock<bool>>,
+    // trueなら保存にかかる時間の分だけFixedDelayの待ち時間を短くする
+    adaptive_timing: Arc<RwLock<bool>>,
+    // Someなら計測した平均の代わりにこの値(ms)を使う
+    latency_override_ms: Arc<RwLock<Option<u64>>>,
+    // 複数指定するとすべて同じチャンネルに流す
+    udp_bind_addrs: Arc<RwLock<Vec<String>>>,
+    // 次に待ち受けを開始したときから反映される
+    udp_framing: Arc<RwLock<UdpFraming>>,
+    // UDPとリプレイ保存イベントのチャンネルの容量。次にシステムを開始したときから反映される
+    channel_capacity: Arc<RwLock<usize>>,
+    // チャンネルが満杯で捨てたメッセージの数
+    udp_channel_drops: Arc<Mutex<u64>>,
+    replay_channel_drops: Arc<Mutex<u64>>,
+    // 動作中のUDP待ち受け（アドレスごと）
+    udp_listeners: Arc<Mutex<HashMap<String, AbortOnDrop>>>,
+    udp_listener_tx: Arc<Mutex<Option<mpsc::Sender<UdpMessage>>>>,
+    // 名前付きの遅延プロファイル（イベント→遅延秒）
+    delay_profiles: Arc<RwLock<HashMap<String, DelayProfile>>>,
+    clip_profiles: Arc<RwLock<HashMap<MugiCmd, ClipProfile>>>,
+    // イベントごとの保存までの遅延(ms)。プロファイルより優先度は低い
+    trigger_delays_ms: Arc<RwLock<HashMap<MugiCmd, u64>>>,
+    // イベントを受けたときに切り替えるシーン
+    trigger_scenes: Arc<RwLock<HashMap<MugiCmd, String>>>,
+    // トリガーごとのOBSの保存先（なければOBSの設定のまま）
+    trigger_output_folders: Arc<RwLock<HashMap<MugiCmd, PathBuf>>>,
+    // 次に保存されたリプレイをトリガーごとの保存先に移す
+    replay_redirect: ReplayRedirect,
+    // イベントを受けたときにスクリーンショットを撮るか（動画の代わりか動画と一緒か）
+    capture_screenshot_on: Arc<RwLock<HashMap<MugiCmd, ScreenshotMode>>>,
+    // 保存後に再エンコードする設定（Noneなら保存したまま）
+    transcode: Arc<RwLock<Option<TranscodeSettings>>>,
+    // Noneならsleep_duration_secを使う
+    active_profile: Arc<RwLock<Option<String>>>,
+    // アプリ内表示用のログ（古い順）
+    recent_logs: Arc<Mutex<VecDeque<String>>>,
+    // UI側の設定。setupでアプリのデータフォルダから読み込む
+    ui_preferences: Arc<Mutex<UiPreferences>>,
+    // 名前付きの再生リスト。setupでアプリのデータフォルダから読み込む
+    playlists: Arc<Mutex<NamedPlaylists>>,
+    // 動作中のシステムにUDPメッセージを流し込む（録画の再生用）
+    udp_msg_tx: Arc<Mutex<Option<mpsc::Sender<UdpMessage>>>>,
+    trigger_recording: SharedRecording,
+    // 送信元IPごとのトリガー時の動作（未設定なら通常通り保存）
+    source_actions: Arc<RwLock<HashMap<IpAddr, SourceAction>>>,
+    // 実行中のトリミング（cancel_exportで止める）
+    export_job: Arc<Mutex<Option<ExportJob>>>,
+    // Someなら実際のOBSの代わりに使う
+    mock_obs: Arc<RwLock<Option<Arc<MockObs>>>>,
+    obs_request_timeout_sec: Arc<RwLock<u64>>,
+    // OBSとのやり取りをtraceログに出すか
+    obs_trace: Arc<RwLock<bool>>,
+    // CONNECTION_FLAP_WINDOW内の接続イベントがこの回数を超えたら1つにまとめる（0で無効）
+    connection_flap_threshold: Arc<RwLock<u32>>,
+    // CONNECTION_FLAP_WINDOW内に起きた接続イベントの時刻（古い順）
+    connection_events: Arc<Mutex<VecDeque<std::time::Instant>>>,
+    // 接続時にOBSのリプレイバッファがこの秒数より短ければ警告する（Noneで確認しない）
+    min_buffer_length_sec: Arc<RwLock<Option<u64>>>,
+    // trueなら警告の代わりにmin_buffer_length_secまで伸ばす
+    raise_buffer_length: Arc<RwLock<bool>>,
+    // 切断時にOBSとタスクの終了を待つ秒数。過ぎたら強制的に破棄する
+    shutdown_timeout_sec: Arc<RwLock<u64>>,
+}
+
+struct ExportJob {
+    task: JoinHandle<()>,
+    index: usize,
+    output: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+enum SourceAction {
+    // 通常通りリプレイを保存
+    Save,
+    // 指定したシーンに切り替える
+    SwitchScene(String),
+    // 何もしない
+    Ignore,
+}
+
+type DelayProfile = HashMap<MugiCmd, u64>;
+
+// イベントごとのクリップの長さ
+// OBSのリプレイバッファの長さは動作中に変えられないので、preは保存後に先頭を切って合わせる
+// そのためバッファの長さより長いpreは指定しても意味がない
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+struct ClipProfile {
+    // トリガーより前に残す秒数（0なら切らずにバッファの長さのまま）
+    pre_secs: u64,
+    // トリガー後に待つ秒数（FixedDelayのsleep_duration_secの代わり）
+    post_secs: u64,
+}
+
+// 保存したクリップを共有しやすい形式に変換する設定（ffmpeg）
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct TranscodeSettings {
+    // ffmpegの-c:vに渡すエンコーダ（libx264など）
+    video_codec: String,
+    video_bitrate_kbps: u32,
+    // 変換後の拡張子（mp4など）
+    container: String,
+    // trueなら元のクリップを消して置き換える
+    replace_original: bool,
+}
+
+// 空き容量がmin_free_space_mbを下回ったときの動作
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+enum DiskFullPolicy {
+    // アプリが保存した古いクリップから削除する
+    DeleteOldest,
+    // 録画を一時停止する
+    PauseCapture,
+}
+
+// OBS切断中に溜めておくトリガーの上限
+const MAX_PENDING_TRIGGERS: usize = 10;
+
+// 再接続時に溜まっていたトリガーをどうするか
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+enum PendingTriggerPolicy {
+    // 1回だけまとめて保存する
+    CatchUpSave,
+    // 捨てる
+    Discard,
+}
+
+// 受信したトリガー
+#[derive(Debug, Clone)]
+struct Trigger {
+    cmd: MugiCmd,
+    received_at: std::time::Instant,
+    // unix time(ms)
+    timestamp: u64,
+    // 受信したUDPポート
+    port: Option<u16>,
+    // sidecarに書き出すタグ
+    tags: HashMap<String, String>,
+    // EpicSaveの直後のScoredなら、まとめる対象のEpicSave
+    combo: Option<ComboCandidate>,
+    sender_skew_ms: Option<i64>,
+}
+
+// 保存済みのEpicSave。combo_window_sec以内にScoredが来たら1つのクリップにまとめる
+#[derive(Debug, Clone)]
+struct ComboCandidate {
+    received_at: std::time::Instant,
+    // unix time(ms)
+    timestamp: u64,
+    path: PathBuf,
+}
+
+// トリガー後、いつ保存するか
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+enum CaptureMode {
+    // sleep_duration_sec待ってから保存
+    FixedDelay,
+    // 次のイベントが来る(かタイムアウトする)まで待ってから保存
+    UntilNextEvent,
+    // OBSで録画し続け、トリガーではセッションにマーカーを書くだけ
+    Continuous,
+}
+
+// スクリーンショットを撮るイベントで動画をどうするか
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+enum ScreenshotMode {
+    // 動画は保存しない
+    Instead,
+    // 動画も保存する
+    Also,
+}
+
+// ログの出力先（カレントディレクトリ基準）
+const LOG_DIR: &str = "./logs";
+// アプリ内表示用に保持するログの行数
+const MAX_RECENT_LOG_LINES: usize = 1000;
+
+// 設定項目の一覧
+#[derive(Serialize, Debug, Clone)]
+struct AppConfig {
+    sleep_duration_sec: u64,
+    shutdown_timeout_sec: u64,
+    min_buffer_length_sec: Option<u64>,
+    raise_buffer_length: bool,
+    startup_delay_sec: u64,
+    webhook_url: Option<String>,
+    trigger_cmds: HashSet<MugiCmd>,
+    trigger_sequence: Option<TriggerSequence>,
+    playback_audio_mode: PlaybackAudioMode,
+    capture_mode: CaptureMode,
+    next_event_timeout_sec: u64,
+    combo_window_sec: u64,
+    heartbeat_timeout_sec: u64,
+    connection_flap_threshold: u32,
+    obs_request_timeout_sec: u64,
+    highlights_scene: Option<String>,
+    highlight_fit: HighlightFit,
+    playback_transform: Option<SourceTransform>,
+    playback_buffering_ms: u64,
+    playback_loop: bool,
+    playback_freeze_intro_ms: u64,
+    hide_when_idle: bool,
+    scene_transition: Option<SceneTransition>,
+    max_clips: Option<usize>,
+    pending_trigger_policy: PendingTriggerPolicy,
+    min_free_space_mb: u64,
+    disk_full_policy: DiskFullPolicy,
+    require_active_output: bool,
+    adaptive_timing: bool,
+    latency_override_ms: Option<u64>,
+    udp_bind_addrs: Vec<String>,
+    udp_framing: UdpFraming,
+    channel_capacity: usize,
+    transition_aware: bool,
+    instant_replay: bool,
+    obs_profile: Option<String>,
+    obs_scene_collection: Option<String>,
+    delay_profiles: HashMap<String, DelayProfile>,
+    clip_profiles: HashMap<MugiCmd, ClipProfile>,
+    trigger_delays_ms: HashMap<MugiCmd, u64>,
+    trigger_scenes: HashMap<MugiCmd, String>,
+    trigger_output_folders: HashMap<MugiCmd, PathBuf>,
+    capture_screenshot_on: HashMap<MugiCmd, ScreenshotMode>,
+    transcode: Option<TranscodeSettings>,
+    active_profile: Option<String>,
+    source_actions: HashMap<IpAddr, SourceAction>,
+}
+
+// チャンネルの容量の初期値
+const DEFAULT_CHANNEL_CAPACITY: usize = 32;
+// 保持するレイテンシの件数
+const MAX_TRIGGER_LATENCIES: usize = 50;
+// 保持するトリガー履歴の件数
+const MAX_TRIGGER_HISTORY: usize = 1000;
+
+struct TriggerHistoryEntry {
+    // unix time(ms)
+    timestamp: u64,
+    cmd: MugiCmd,
+    source: Option<IpAddr>,
+    tags: HashMap<String, String>,
+}
+
+// 新しい山場の位置をどれだけ重視するか
+const PEAK_EMA_ALPHA: f64 = 0.3;
+
+// トリガーからハイライトの山場までの時間(ms)の指数移動平均
+#[derive(Default, Clone, Copy)]
+struct PeakOffsetEma {
+    offset_ms: f64,
+    samples: u32,
+}
+
+impl PeakOffsetEma {
+    fn push(&mut self, offset_ms: f64) {
+        self.offset_ms = if self.samples == 0 {
+            offset_ms
+        } else {
+            PEAK_EMA_ALPHA * offset_ms + (1.0 - PEAK_EMA_ALPHA) * self.offset_ms
+        };
+        self.samples += 1;
+    }
+}
+
+#[derive(Serialize)]
+struct SuggestedDelay {
+    delay_sec: u64,
+    // 山場がトリガーより前ならマイナス
+    offset_ms: i64,
+    samples: u32,
+    current_delay_sec: u64,
+}
+
+// UDP受信からOBSの保存完了までの遅延（意図的なsleepは除く）
+#[derive(Serialize, Clone)]
+struct TriggerLatency {
+    cmd: MugiCmd,
+    port: Option<u16>,
+    // unix time(ms)
+    timestamp: u64,
+    latency_ms: u64,
+    // 受信時刻 - 送信側のts。送信側の時計とのずれを含む
+    sender_skew_ms: Option<i64>,
 }
 
 impl AppState {
@@ -29,133 +434,4048 @@ impl AppState {
         Self {
             obs_connection_info: Arc::new(Mutex::new(None)),
             is_system_running: Arc::new(Mutex::new(false)),
+            is_connecting: Arc::new(Mutex::new(false)),
+            is_capture_paused: Arc::new(Mutex::new(false)),
+            is_udp_processing: Arc::new(Mutex::new(true)),
+            is_playback_unavailable: Arc::new(Mutex::new(false)),
+            is_capturing_clip: Arc::new(Mutex::new(false)),
             sleep_duration_sec: Arc::new(RwLock::new(3)), // デフォルト3秒
+            startup_delay_sec: Arc::new(RwLock::new(0)),
+            launched_at: std::time::Instant::now(),
+            pending_save: Arc::new(Mutex::new(None)),
+            system_task: Arc::new(Mutex::new(None)),
+            webhook_url: Arc::new(RwLock::new(None)),
+            trigger_cmds: Arc::new(RwLock::new(HashSet::from([
+                MugiCmd::Scored,
+                MugiCmd::EpicSave,
+            ]))),
+            trigger_sequence: Arc::new(RwLock::new(None)),
+            playback_audio_mode: Arc::new(RwLock::new(PlaybackAudioMode::Normal)),
+            clips: Arc::new(Mutex::new(Vec::new())),
+            trigger_latencies: Arc::new(Mutex::new(VecDeque::new())),
+            trigger_history: Arc::new(Mutex::new(VecDeque::new())),
+            peak_offset: Arc::new(Mutex::new(PeakOffsetEma::default())),
+            capture_mode: Arc::new(RwLock::new(CaptureMode::FixedDelay)),
+            next_event_timeout_sec: Arc::new(RwLock::new(10)), // デフォルト10秒
+            combo_window_sec: Arc::new(RwLock::new(0)),
+            heartbeat_timeout_sec: Arc::new(RwLock::new(0)),
+            highlights_scene: Arc::new(RwLock::new(None)),
+            highlight_fit: Arc::new(RwLock::new(HighlightFit::None)),
+            playback_transform: Arc::new(RwLock::new(None)),
+            playback_buffering_ms: Arc::new(RwLock::new(100)),
+            playback_loop: Arc::new(RwLock::new(false)),
+            playback_playlist: Arc::new(Mutex::new(Vec::new())),
+            playback_deadline: Arc::new(Mutex::new(None)),
+            playback_freeze_intro_ms: Arc::new(RwLock::new(0)),
+            window_queue: Arc::new(Mutex::new(VecDeque::new())),
+            window_clip: Arc::new(Mutex::new(None)),
+            hide_when_idle: Arc::new(RwLock::new(true)),
+            scene_transition: Arc::new(RwLock::new(None)),
+            created_clips: Arc::new(Mutex::new(VecDeque::new())),
+            max_clips: Arc::new(RwLock::new(None)),
+            session: Arc::new(Mutex::new(None)),
+            session_counter: Arc::new(Mutex::new(None)),
+            obs: Arc::new(Mutex::new(None)),
+            save_lock: Arc::new(tokio::sync::Mutex::new(())),
+            transcode_lock: Arc::new(tokio::sync::Mutex::new(())),
+            transition_aware: Arc::new(RwLock::new(false)),
+            instant_replay: Arc::new(RwLock::new(false)),
+            obs_profile: Arc::new(RwLock::new(None)),
+            obs_scene_collection: Arc::new(RwLock::new(None)),
+            obs_event_listener: Arc::new(Mutex::new(None)),
+            replay_path_tx: Arc::new(Mutex::new(None)),
+            pending_triggers: Arc::new(Mutex::new(VecDeque::new())),
+            pending_trigger_policy: Arc::new(RwLock::new(PendingTriggerPolicy::CatchUpSave)),
+            min_free_space_mb: Arc::new(RwLock::new(0)), // デフォルトは確認しない
+            disk_full_policy: Arc::new(RwLock::new(DiskFullPolicy::PauseCapture)),
+            require_active_output: Arc::new(RwLock::new(false)),
+            adaptive_timing: Arc::new(RwLock::new(false)),
+            latency_override_ms: Arc::new(RwLock::new(None)),
+            udp_bind_addrs: Arc::new(RwLock::new(vec![DEFAULT_BIND_ADDR.to_string()])),
+            udp_framing: Arc::new(RwLock::new(UdpFraming::default())),
+            channel_capacity: Arc::new(RwLock::new(DEFAULT_CHANNEL_CAPACITY)),
+            udp_channel_drops: Arc::new(Mutex::new(0)),
+            replay_channel_drops: Arc::new(Mutex::new(0)),
+            udp_listeners: Arc::new(Mutex::new(HashMap::new())),
+            udp_listener_tx: Arc::new(Mutex::new(None)),
+            delay_profiles: Arc::new(RwLock::new(HashMap::new())),
+            clip_profiles: Arc::new(RwLock::new(HashMap::new())),
+            trigger_delays_ms: Arc::new(RwLock::new(HashMap::new())),
+            trigger_scenes: Arc::new(RwLock::new(HashMap::new())),
+            trigger_output_folders: Arc::new(RwLock::new(HashMap::new())),
+            replay_redirect: Arc::new(Mutex::new(None)),
+            capture_screenshot_on: Arc::new(RwLock::new(HashMap::new())),
+            transcode: Arc::new(RwLock::new(None)),
+            active_profile: Arc::new(RwLock::new(None)),
+            recent_logs: Arc::new(Mutex::new(VecDeque::new())),
+            ui_preferences: Arc::new(Mutex::new(UiPreferences::default())),
+            playlists: Arc::new(Mutex::new(NamedPlaylists::default())),
+            udp_msg_tx: Arc::new(Mutex::new(None)),
+            trigger_recording: Arc::new(Mutex::new(None)),
+            source_actions: Arc::new(RwLock::new(HashMap::new())),
+            export_job: Arc::new(Mutex::new(None)),
+            mock_obs: Arc::new(RwLock::new(None)),
+            obs_request_timeout_sec: Arc::new(RwLock::new(obs::DEFAULT_REQUEST_TIMEOUT_SEC)),
+            obs_trace: Arc::new(RwLock::new(false)),
+            connection_flap_threshold: Arc::new(RwLock::new(3)),
+            connection_events: Arc::new(Mutex::new(VecDeque::new())),
+            min_buffer_length_sec: Arc::new(RwLock::new(None)),
+            raise_buffer_length: Arc::new(RwLock::new(false)),
+            shutdown_timeout_sec: Arc::new(RwLock::new(3)),
+        }
+    }
+
+    fn config(&self) -> AppConfig {
+        AppConfig {
+            sleep_duration_sec: *self.sleep_duration_sec.read().unwrap(),
+            shutdown_timeout_sec: *self.shutdown_timeout_sec.read().unwrap(),
+            min_buffer_length_sec: *self.min_buffer_length_sec.read().unwrap(),
+            raise_buffer_length: *self.raise_buffer_length.read().unwrap(),
+            startup_delay_sec: *self.startup_delay_sec.read().unwrap(),
+            webhook_url: self.webhook_url.read().unwrap().clone(),
+            trigger_cmds: self.trigger_cmds.read().unwrap().clone(),
+            trigger_sequence: self.trigger_sequence.read().unwrap().clone(),
+            playback_audio_mode: *self.playback_audio_mode.read().unwrap(),
+            capture_mode: *self.capture_mode.read().unwrap(),
+            next_event_timeout_sec: *self.next_event_timeout_sec.read().unwrap(),
+            combo_window_sec: *self.combo_window_sec.read().unwrap(),
+            heartbeat_timeout_sec: *self.heartbeat_timeout_sec.read().unwrap(),
+            connection_flap_threshold: *self.connection_flap_threshold.read().unwrap(),
+            obs_request_timeout_sec: *self.obs_request_timeout_sec.read().unwrap(),
+            highlights_scene: self.highlights_scene.read().unwrap().clone(),
+            highlight_fit: *self.highlight_fit.read().unwrap(),
+            playback_transform: *self.playback_transform.read().unwrap(),
+            playback_buffering_ms: *self.playback_buffering_ms.read().unwrap(),
+            playback_loop: *self.playback_loop.read().unwrap(),
+            playback_freeze_intro_ms: *self.playback_freeze_intro_ms.read().unwrap(),
+            hide_when_idle: *self.hide_when_idle.read().unwrap(),
+            scene_transition: self.scene_transition.read().unwrap().clone(),
+            max_clips: *self.max_clips.read().unwrap(),
+            pending_trigger_policy: *self.pending_trigger_policy.read().unwrap(),
+            min_free_space_mb: *self.min_free_space_mb.read().unwrap(),
+            disk_full_policy: *self.disk_full_policy.read().unwrap(),
+            require_active_output: *self.require_active_output.read().unwrap(),
+            adaptive_timing: *self.adaptive_timing.read().unwrap(),
+            latency_override_ms: *self.latency_override_ms.read().unwrap(),
+            udp_bind_addrs: self.udp_bind_addrs.read().unwrap().clone(),
+            udp_framing: *self.udp_framing.read().unwrap(),
+            channel_capacity: *self.channel_capacity.read().unwrap(),
+            transition_aware: *self.transition_aware.read().unwrap(),
+            instant_replay: *self.instant_replay.read().unwrap(),
+            obs_profile: self.obs_profile.read().unwrap().clone(),
+            obs_scene_collection: self.obs_scene_collection.read().unwrap().clone(),
+            delay_profiles: self.delay_profiles.read().unwrap().clone(),
+            clip_profiles: self.clip_profiles.read().unwrap().clone(),
+            trigger_delays_ms: self.trigger_delays_ms.read().unwrap().clone(),
+            trigger_scenes: self.trigger_scenes.read().unwrap().clone(),
+            trigger_output_folders: self.trigger_output_folders.read().unwrap().clone(),
+            capture_screenshot_on: self.capture_screenshot_on.read().unwrap().clone(),
+            transcode: self.transcode.read().unwrap().clone(),
+            active_profile: self.active_profile.read().unwrap().clone(),
+            source_actions: self.source_actions.read().unwrap().clone(),
+        }
+    }
+
+    // 設定項目だけを初期値に戻す（接続やセッションはそのまま）
+    fn reset_config(&self) {
+        let d = AppState::new().config();
+        *self.sleep_duration_sec.write().unwrap() = d.sleep_duration_sec;
+        *self.shutdown_timeout_sec.write().unwrap() = d.shutdown_timeout_sec;
+        *self.min_buffer_length_sec.write().unwrap() = d.min_buffer_length_sec;
+        *self.raise_buffer_length.write().unwrap() = d.raise_buffer_length;
+        *self.startup_delay_sec.write().unwrap() = d.startup_delay_sec;
+        *self.webhook_url.write().unwrap() = d.webhook_url;
+        *self.trigger_cmds.write().unwrap() = d.trigger_cmds;
+        *self.trigger_sequence.write().unwrap() = d.trigger_sequence;
+        *self.playback_audio_mode.write().unwrap() = d.playback_audio_mode;
+        *self.capture_mode.write().unwrap() = d.capture_mode;
+        *self.next_event_timeout_sec.write().unwrap() = d.next_event_timeout_sec;
+        *self.combo_window_sec.write().unwrap() = d.combo_window_sec;
+        *self.heartbeat_timeout_sec.write().unwrap() = d.heartbeat_timeout_sec;
+        *self.connection_flap_threshold.write().unwrap() = d.connection_flap_threshold;
+        *self.obs_request_timeout_sec.write().unwrap() = d.obs_request_timeout_sec;
+        *self.highlights_scene.write().unwrap() = d.highlights_scene;
+        *self.highlight_fit.write().unwrap() = d.highlight_fit;
+        *self.playback_transform.write().unwrap() = d.playback_transform;
+        *self.playback_buffering_ms.write().unwrap() = d.playback_buffering_ms;
+        *self.playback_loop.write().unwrap() = d.playback_loop;
+        *self.playback_freeze_intro_ms.write().unwrap() = d.playback_freeze_intro_ms;
+        *self.hide_when_idle.write().unwrap() = d.hide_when_idle;
+        *self.scene_transition.write().unwrap() = d.scene_transition;
+        *self.max_clips.write().unwrap() = d.max_clips;
+        *self.pending_trigger_policy.write().unwrap() = d.pending_trigger_policy;
+        *self.min_free_space_mb.write().unwrap() = d.min_free_space_mb;
+        *self.disk_full_policy.write().unwrap() = d.disk_full_policy;
+        *self.require_active_output.write().unwrap() = d.require_active_output;
+        *self.adaptive_timing.write().unwrap() = d.adaptive_timing;
+        *self.latency_override_ms.write().unwrap() = d.latency_override_ms;
+        *self.udp_bind_addrs.write().unwrap() = d.udp_bind_addrs;
+        *self.udp_framing.write().unwrap() = d.udp_framing;
+        *self.channel_capacity.write().unwrap() = d.channel_capacity;
+        *self.transition_aware.write().unwrap() = d.transition_aware;
+        *self.instant_replay.write().unwrap() = d.instant_replay;
+        *self.obs_profile.write().unwrap() = d.obs_profile;
+        *self.obs_scene_collection.write().unwrap() = d.obs_scene_collection;
+        *self.delay_profiles.write().unwrap() = d.delay_profiles;
+        *self.clip_profiles.write().unwrap() = d.clip_profiles;
+        *self.trigger_delays_ms.write().unwrap() = d.trigger_delays_ms;
+        *self.trigger_scenes.write().unwrap() = d.trigger_scenes;
+        *self.trigger_output_folders.write().unwrap() = d.trigger_output_folders;
+        *self.capture_screenshot_on.write().unwrap() = d.capture_screenshot_on;
+        *self.transcode.write().unwrap() = d.transcode;
+        *self.active_profile.write().unwrap() = d.active_profile;
+        *self.source_actions.write().unwrap() = d.source_actions;
+    }
+
+    // 前回設定した起動時の接続待ちを読む（環境変数があればそちらで上書きする）
+    fn apply_preferences(&self) {
+        match preferences::load() {
+            Ok(prefs) => {
+                if let Some(delay) = prefs.startup_delay_sec {
+                    *self.startup_delay_sec.write().unwrap() = delay.min(MAX_STARTUP_DELAY_SEC);
+                }
+            }
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    // 環境変数で初期設定を上書きする（未設定・不正な値はデフォルトのまま）
+    fn apply_env_config(&self) {
+        if let Ok(value) = std::env::var("RL_REPLAY_SLEEP_SEC") {
+            match value.parse::<u64>() {
+                Ok(duration) => {
+                    let clamped_duration = duration.clamp(1, 30); // 1-30秒の範囲制限
+                    *self.sleep_duration_sec.write().unwrap() = clamped_duration;
+                    info!(
+                        "sleep duration from RL_REPLAY_SLEEP_SEC: {}s",
+                        clamped_duration
+                    );
+                }
+                Err(e) => warn!("Invalid RL_REPLAY_SLEEP_SEC {:?}: {}", value, e),
+            }
+        }
+        if let Ok(value) = std::env::var("RL_REPLAY_STARTUP_DELAY_SEC") {
+            match value.parse::<u64>() {
+                Ok(delay) => {
+                    let clamped_delay = delay.min(MAX_STARTUP_DELAY_SEC);
+                    *self.startup_delay_sec.write().unwrap() = clamped_delay;
+                    info!(
+                        "startup delay from RL_REPLAY_STARTUP_DELAY_SEC: {}s",
+                        clamped_delay
+                    );
+                }
+                Err(e) => warn!("Invalid RL_REPLAY_STARTUP_DELAY_SEC {:?}: {}", value, e),
+            }
+        }
+        // カンマ区切り (例: "scored,epicSave")
+        if let Ok(value) = std::env::var("RL_REPLAY_TRIGGERS") {
+            let cmds: Result<HashSet<MugiCmd>, _> = value
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(MugiCmd::from_str)
+                .collect();
+            match cmds {
+                Ok(cmds) => {
+                    info!("trigger commands from RL_REPLAY_TRIGGERS: {:?}", cmds);
+                    *self.trigger_cmds.write().unwrap() = cmds;
+                }
+                Err(e) => warn!("Invalid RL_REPLAY_TRIGGERS {:?}: {}", value, e),
+            }
+        }
+        // カンマ区切りで複数指定できる（1つでも不正ならデフォルトのまま）
+        if let Ok(value) = std::env::var("RL_REPLAY_UDP_ADDR") {
+            let addrs: Result<Vec<std::net::SocketAddr>, _> = value
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::parse)
+                .collect();
+            match addrs {
+                Ok(addrs) if addrs.is_empty() => {
+                    warn!("RL_REPLAY_UDP_ADDR is empty, keep the defaults")
+                }
+                Ok(addrs) => {
+                    info!("UDP bind addresses from RL_REPLAY_UDP_ADDR: {:?}", addrs);
+                    *self.udp_bind_addrs.write().unwrap() =
+                        addrs.iter().map(ToString::to_string).collect();
+                }
+                Err(e) => warn!("Invalid RL_REPLAY_UDP_ADDR {:?}: {}", value, e),
+            }
+        }
+    }
+}
+
+const MAX_STARTUP_DELAY_SEC: u64 = 120;
+
+// 起動からstartup_delay_secが経つまで待つ（それ以降の接続はすぐ行う）
+async fn wait_startup_delay(state: &AppState) {
+    let delay = std::time::Duration::from_secs(*state.startup_delay_sec.read().unwrap());
+    let remaining = delay.saturating_sub(state.launched_at.elapsed());
+    if !remaining.is_zero() {
+        info!("Waiting {:?} for OBS to start up", remaining);
+        tokio::time::sleep(remaining).await;
+    }
+}
+
+// unix time(ms)
+fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+// アプリが保存したクリップを記録し、上限を超えた古いものを削除する
+// OBSから直接保存されたものなど、記録していないファイルは消さない
+fn track_created_clip(state: &AppState, path: PathBuf) {
+    let max_clips = *state.max_clips.read().unwrap();
+    let removed: Vec<PathBuf> = {
+        let mut created_clips = state.created_clips.lock().unwrap();
+        created_clips.push_back(path);
+        match max_clips {
+            Some(max) if created_clips.len() > max => {
+                let excess = created_clips.len() - max;
+                created_clips.drain(..excess).collect()
+            }
+            _ => Vec::new(),
+        }
+    };
+
+    for old in removed {
+        remove_clip_file(state, &old);
+    }
+}
+
+// クリップとsidecarを削除して一覧からも外す
+fn remove_clip_file(state: &AppState, path: &Path) {
+    info!("Removing old clip: {:?}", path);
+    if let Err(e) = std::fs::remove_file(path) {
+        error!("Failed to remove old clip {:?}: {}", path, e);
+    }
+    let _ = std::fs::remove_file(sidecar::sidecar_path(path));
+    state.clips.lock().unwrap().retain(|p| p != path);
+}
+
+// アプリが保存したクリップを古い順にbytes以上空くまで削除し、空けた容量を返す
+fn delete_oldest_clips(state: &AppState, bytes: u64) -> u64 {
+    let mut freed = 0;
+    while freed < bytes {
+        let oldest = state.created_clips.lock().unwrap().pop_front();
+        let Some(oldest) = oldest else {
+            break;
+        };
+        freed += std::fs::metadata(&oldest).map(|m| m.len()).unwrap_or(0);
+        remove_clip_file(state, &oldest);
+    }
+    freed
+}
+
+#[derive(Serialize, Clone)]
+struct DiskSpaceLowPayload {
+    free_mb: u64,
+    min_free_mb: u64,
+    policy: DiskFullPolicy,
+}
+
+impl AppEvent for DiskSpaceLowPayload {
+    const NAME: &'static str = "disk-space-low";
+}
+
+// 保存前に空き容量を確認する。保存してよければtrue
+async fn ensure_disk_space(obs: &obs::Obs, state: &AppState, app_handle: &AppHandle) -> bool {
+    let min_free_mb = *state.min_free_space_mb.read().unwrap();
+    if min_free_mb == 0 {
+        return true;
+    }
+    let free_mb = match obs.get_available_disk_space_mb().await {
+        Ok(free_mb) => free_mb,
+        Err(e) => {
+            warn!("Failed to check disk space: {}", e);
+            return true;
+        }
+    };
+    if free_mb >= min_free_mb {
+        return true;
+    }
+
+    let policy = *state.disk_full_policy.read().unwrap();
+    warn!(
+        "Disk space low: {}MB free (min {}MB), policy {:?}",
+        free_mb, min_free_mb, policy
+    );
+    let payload = DiskSpaceLowPayload {
+        free_mb,
+        min_free_mb,
+        policy,
+    };
+    EventSender::new(app_handle.clone()).emit(payload);
+
+    match policy {
+        DiskFullPolicy::DeleteOldest => {
+            let needed = (min_free_mb - free_mb) * 1024 * 1024;
+            let freed = delete_oldest_clips(state, needed);
+            info!("Freed {} bytes by deleting old clips", freed);
+            true
+        }
+        DiskFullPolicy::PauseCapture => {
+            let mut is_paused = state.is_capture_paused.lock().unwrap();
+            *is_paused = true;
+            false
+        }
+    }
+}
+
+fn add_session_clip(state: &AppState, clip: SessionClip) {
+    {
+        let mut session = state.session.lock().unwrap();
+        if let Some(session) = session.as_mut() {
+            session.clips.push(clip);
+        }
+    }
+    let mut counter = state.session_counter.lock().unwrap();
+    if let Some(counter) = counter.as_mut() {
+        counter.add_clip();
+    }
+}
+
+fn reset_session_counter(state: &AppState, next: Option<SessionCounter>) {
+    let mut counter = state.session_counter.lock().unwrap();
+    *counter = next;
+}
+
+// 現在のセッションを終了してsession.jsonを書き出す
+// nextがあれば新しいセッションを始める
+fn finish_session(state: &AppState, next: Option<SessionManifest>) {
+    let finished = {
+        let mut session = state.session.lock().unwrap();
+        std::mem::replace(&mut *session, next)
+    };
+    if let Some(mut finished) = finished {
+        match finished.finish(unix_millis()) {
+            Ok(path) => info!("Session manifest written: {:?}", path),
+            Err(e) => error!("{}", e),
+        }
+    }
+}
+
+fn current_obs(state: &AppState) -> Option<Arc<obs::Obs>> {
+    state.obs.lock().unwrap().clone()
+}
+
+// OBS接続をシステムに組み込む（イベントリスナーも張り直す）
+async fn link_obs(
+    state: &AppState,
+    app_handle: &AppHandle,
+    obs: obs::Obs,
+    rb_tx: mpsc::Sender<PathBuf>,
+) -> Result<(), String> {
+    let overflow = OverflowReporter::new(
+        "replay",
+        state.replay_channel_drops.clone(),
+        EventSender::new(app_handle.clone()),
+    );
+    let event_listener = obs
+        .set_event_listener(rb_tx.clone(), overflow)
+        .await
+        .map(AbortOnDrop)
+        .map_err(|e| format!("Failed to set event listener: {}", e))?;
+    {
+        let mut listener = state.obs_event_listener.lock().unwrap();
+        *listener = Some(event_listener);
+    }
+    {
+        let mut replay_path_tx = state.replay_path_tx.lock().unwrap();
+        *replay_path_tx = Some(rb_tx);
+    }
+    {
+        let mut current = state.obs.lock().unwrap();
+        *current = Some(Arc::new(obs));
+    }
+    Ok(())
+}
+
+// OBS切断中のトリガーを溜める（上限を超えたら古いものから捨てる）
+fn buffer_trigger(state: &AppState, trigger: Trigger) {
+    let mut pending = state.pending_triggers.lock().unwrap();
+    if pending.len() >= MAX_PENDING_TRIGGERS {
+        pending.pop_front();
+    }
+    let cmd = trigger.cmd;
+    pending.push_back(trigger);
+    warn!(
+        "OBS is not connected, buffered {:?} ({} pending)",
+        cmd,
+        pending.len()
+    );
+}
+
+// 再接続後に溜まっていたトリガーを処理する
+fn drain_pending_triggers(state: &AppState, app_handle: &AppHandle) {
+    let pending: Vec<Trigger> = state.pending_triggers.lock().unwrap().drain(..).collect();
+    let Some(latest) = pending.last().cloned() else {
+        return;
+    };
+    let policy = *state.pending_trigger_policy.read().unwrap();
+    match policy {
+        PendingTriggerPolicy::CatchUpSave => {
+            info!("Catch-up save for {} pending triggers", pending.len());
+            tokio::spawn(delayed_save(
+                state.clone(),
+                app_handle.clone(),
+                latest,
+                std::time::Duration::ZERO,
+            ));
         }
+        PendingTriggerPolicy::Discard => {
+            info!("Discarded {} pending triggers", pending.len());
+        }
+    }
+}
+
+fn unlink_obs(state: &AppState) {
+    state.obs.lock().unwrap().take();
+    state.obs_event_listener.lock().unwrap().take();
+    state.replay_path_tx.lock().unwrap().take();
+}
+
+// drop時にタスクを止める
+struct AbortOnDrop(JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+// 同じチャンネルに流すUDP待ち受けを追加する（落ちたら再bindする）
+fn spawn_udp_listener(
+    state: &AppState,
+    addr: String,
+    app_handle: &AppHandle,
+) -> Result<(), String> {
+    let tx = state.udp_listener_tx.lock().unwrap().clone();
+    let Some(tx) = tx else {
+        return Err("システムは動作していません".to_string());
+    };
+    let mut listeners = state.udp_listeners.lock().unwrap();
+    if listeners.contains_key(&addr) {
+        return Err(format!("{}は既に待ち受けています", addr));
     }
+    let framing = *state.udp_framing.read().unwrap();
+    let task = tokio::spawn(supervise_socket(
+        addr.clone(),
+        tx,
+        framing,
+        EventSender::new(app_handle.clone()),
+        state.udp_channel_drops.clone(),
+    ));
+    listeners.insert(addr, AbortOnDrop(task));
+    Ok(())
+}
+
+#[derive(Serialize, Clone)]
+struct SaveCancelledPayload;
+
+impl AppEvent for SaveCancelledPayload {
+    const NAME: &'static str = "save-cancelled";
+}
+
+// 遅延中の保存があればキャンセルして通知。キャンセルしたらtrue
+fn abort_pending_save(state: &AppState, app_handle: &AppHandle) -> bool {
+    let pending = state.pending_save.lock().unwrap().take();
+    let Some(pending) = pending.filter(|p| !p.is_finished()) else {
+        return false;
+    };
+    pending.abort();
+    info!("Pending replay save cancelled");
+    EventSender::new(app_handle.clone()).emit(SaveCancelledPayload);
+    true
 }
 
+// 誤ったトリガーで待機中の保存を取り消す（システムは止めない）
 #[tauri::command]
-async fn get_sleep_duration(state: tauri::State<'_, AppState>) -> Result<u64, String> {
-    let sleep_dur = state.sleep_duration_sec.read().unwrap();
-    Ok(*sleep_dur)
+async fn cancel_pending_save(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    if !abort_pending_save(&state, &app_handle) {
+        return Err("待機中の保存はありません".to_string());
+    }
+    Ok("待機中の保存をキャンセルしました".to_string())
 }
 
 #[tauri::command]
-async fn set_sleep_duration(
-    duration: u64,
+async fn pause_capture(
     state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
 ) -> Result<String, String> {
-    let clamped_duration = duration.max(1).min(30); // 1-30秒の範囲制限
+    {
+        let mut is_paused = state.is_capture_paused.lock().unwrap();
+        *is_paused = true;
+    }
+    abort_pending_save(&state, &app_handle);
+    Ok("録画を一時停止しました".to_string())
+}
+
+#[tauri::command]
+async fn resume_capture(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let mut is_paused = state.is_capture_paused.lock().unwrap();
+    *is_paused = false;
+    Ok("録画を再開しました".to_string())
+}
+
+// pause_captureと違い、End/Startなども含めて全てのコマンドを無視する
+#[tauri::command]
+async fn set_udp_processing(
+    enabled: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    *state.is_udp_processing.lock().unwrap() = enabled;
+    let status = if enabled { "有効" } else { "無効" };
+    Ok(format!("UDPコマンドの処理を{}にしました", status))
+}
+
+#[derive(Serialize, Clone)]
+struct UdpIgnoredPayload {
+    // システム起動からの無視した件数
+    ignored_count: u64,
+    source: Option<IpAddr>,
+}
+
+impl AppEvent for UdpIgnoredPayload {
+    const NAME: &'static str = "udp-ignored";
+}
+
+#[tauri::command]
+async fn disconnect_obs(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let system_task = state.system_task.lock().unwrap().take();
+    let Some(system_task) = system_task else {
+        return Err("システムは動作していません".to_string());
+    };
+
+    abort_pending_save(&state, &app_handle);
+    // UDP受信はrun_main_system内でAbortOnDropにより止まる
+    system_task.abort();
+    let obs = state.obs.lock().unwrap().take();
+    enter_idle(&state);
 
     {
-        let mut sleep_dur = state.sleep_duration_sec.write().unwrap();
-        *sleep_dur = clamped_duration;
+        let mut conn_info = state.obs_connection_info.lock().unwrap();
+        *conn_info = None;
+    }
+
+    // OBSが固まっていても切断できるように、待つのは一定時間まで
+    let timeout = std::time::Duration::from_secs(*state.shutdown_timeout_sec.read().unwrap());
+    let shutdown = async {
+        if let Err(e) = system_task.await
+            && !e.is_cancelled()
+        {
+            error!("System task failed: {}", e);
+        }
+        // 他のタスクがまだ持っていれば、最後に手放したときに閉じられる
+        if let Some(obs) = obs.and_then(Arc::into_inner) {
+            obs.disconnect().await;
+        }
+    };
+    if tokio::time::timeout(timeout, shutdown).await.is_err() {
+        warn!(
+            "OBS did not shut down within {:?}, forced disconnect",
+            timeout
+        );
+        return Ok("OBSが応答しないため強制的に切断しました".to_string());
     }
 
+    info!("Disconnected from OBS");
+    Ok("OBSから切断しました".to_string())
+}
+
+#[tauri::command]
+async fn set_shutdown_timeout(
+    timeout_sec: u64,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let clamped_timeout = timeout_sec.clamp(1, 30); // 1-30秒の範囲制限
+    *state.shutdown_timeout_sec.write().unwrap() = clamped_timeout;
     Ok(format!(
-        "録画遅延時間を{}秒に設定しました",
-        clamped_duration
+        "切断のタイムアウトを{}秒に設定しました",
+        clamped_timeout
     ))
 }
 
+// 未接続時はタスクを何も残さない
+fn enter_idle(state: &AppState) {
+    state.system_task.lock().unwrap().take();
+    state.udp_msg_tx.lock().unwrap().take();
+    state.udp_listener_tx.lock().unwrap().take();
+    state.udp_listeners.lock().unwrap().clear();
+    unlink_obs(state);
+    finish_session(state, None);
+    reset_session_counter(state, None);
+    {
+        let mut is_running = state.is_system_running.lock().unwrap();
+        *is_running = false;
+    }
+    info!("System is idle");
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum SystemStatus {
+    Idle,
+    Running,
+    Paused,
+    // 保存はできるが再生はできない（VLCソースの初期化に失敗）
+    Degraded,
+}
+
 #[tauri::command]
-async fn play_highlights(
-    video_paths: Vec<String>,
+async fn get_system_status(state: tauri::State<'_, AppState>) -> Result<SystemStatus, String> {
+    let is_running = *state.is_system_running.lock().unwrap();
+    let is_paused = *state.is_capture_paused.lock().unwrap();
+    let is_degraded = *state.is_playback_unavailable.lock().unwrap();
+    Ok(match (is_running, is_paused, is_degraded) {
+        (false, _, _) => SystemStatus::Idle,
+        (true, true, _) => SystemStatus::Paused,
+        (true, false, true) => SystemStatus::Degraded,
+        (true, false, false) => SystemStatus::Running,
+    })
+}
+
+#[derive(Serialize, Clone)]
+struct AppInfo {
+    version: &'static str,
+    // unix time(ms)
+    build_timestamp: u64,
+    // git rev-parse --short HEAD（取れなければunknown）
+    commit_hash: &'static str,
+    tauri_version: &'static str,
+}
+
+// フロントエンドの表示や不具合報告用
+#[tauri::command]
+async fn get_app_info() -> Result<AppInfo, String> {
+    Ok(AppInfo {
+        version: env!("CARGO_PKG_VERSION"),
+        build_timestamp: env!("BUILD_TIMESTAMP").parse().unwrap_or_default(),
+        commit_hash: env!("GIT_COMMIT_HASH"),
+        tauri_version: tauri::VERSION,
+    })
+}
+
+// UDP受信を止めずにOBSだけ再接続する
+// fullならタスクをすべて止めて、保存済みの接続情報で接続からやり直す
+#[tauri::command]
+async fn reconnect_obs(
+    full: Option<bool>,
     state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
 ) -> Result<String, String> {
-    if video_paths.is_empty() {
-        return Ok("再生する動画がありません".to_string());
+    if full.unwrap_or(false) {
+        return restart_with_stored_credentials(state, app_handle).await;
     }
+    relink_obs(&state, &app_handle).await?;
+    Ok("OBSに再接続しました".to_string())
+}
 
-    // OBS接続情報を取得
-    let (host, port, password) = {
-        let conn_info = state.obs_connection_info.lock().unwrap();
-        match conn_info.as_ref() {
-            Some((host, port, password)) => (host.clone(), *port, password.clone()),
-            None => return Err("OBS接続情報が見つかりません".to_string()),
+async fn restart_with_stored_credentials(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let conn_info = state.obs_connection_info.lock().unwrap().clone();
+    let Some((host, port, password, secure)) = conn_info else {
+        return Err("OBS接続情報が見つかりません".to_string());
+    };
+
+    let system_task = state.system_task.lock().unwrap().take();
+    if let Some(system_task) = system_task {
+        abort_pending_save(&state, &app_handle);
+        system_task.abort();
+        enter_idle(&state);
+    }
+
+    info!("Restarting system with stored credentials");
+    connect_obs(host, port, password, Some(secure), state, app_handle).await
+}
+
+async fn relink_obs(state: &AppState, app_handle: &AppHandle) -> Result<(), String> {
+    if state.replay_path_tx.lock().unwrap().is_none() {
+        return Err("システムは動作していません".to_string());
+    }
+
+    // 再接続中のトリガーは溜めておく
+    state.obs.lock().unwrap().take();
+    let obs = connect_stored_obs(state).await?;
+    relink_connected_obs(state, app_handle, obs).await
+}
+
+// 接続済みのOBSに差し替える
+async fn relink_connected_obs(
+    state: &AppState,
+    app_handle: &AppHandle,
+    obs: obs::Obs,
+) -> Result<(), String> {
+    let rb_tx = state.replay_path_tx.lock().unwrap().clone();
+    let Some(rb_tx) = rb_tx else {
+        return Err("システムは動作していません".to_string());
+    };
+
+    state.obs.lock().unwrap().take();
+    obs.set_replay_buffer()
+        .await
+        .map_err(|e| format!("Failed to set replay buffer: {}", e))?;
+    init_vlc_source_or_degrade(&obs, state).await;
+    link_obs(state, app_handle, obs, rb_tx).await?;
+    drain_pending_triggers(state, app_handle);
+
+    info!("Reconnected to OBS");
+    emit_connection_event(state, app_handle, ObsReconnectedPayload);
+    Ok(())
+}
+
+// 接続イベントを数える範囲
+const CONNECTION_FLAP_WINDOW: std::time::Duration = std::time::Duration::from_secs(60);
+
+#[derive(Serialize, Clone)]
+struct ObsReconnectedPayload;
+
+impl AppEvent for ObsReconnectedPayload {
+    const NAME: &'static str = "obs-reconnected";
+}
+
+#[derive(Serialize, Clone)]
+struct ConnectionUnstablePayload {
+    events: usize,
+    window_sec: u64,
+}
+
+impl AppEvent for ConnectionUnstablePayload {
+    const NAME: &'static str = "obs-connection-unstable";
+}
+
+// 切断・再接続を繰り返しているときは個別に通知せず、1回だけobs-connection-unstableを送る
+// CONNECTION_FLAP_WINDOWの間イベントがなければ元に戻る
+fn emit_connection_event<E: AppEvent>(state: &AppState, app_handle: &AppHandle, payload: E) {
+    let threshold = *state.connection_flap_threshold.read().unwrap() as usize;
+    let now = std::time::Instant::now();
+    let count = {
+        let mut events = state.connection_events.lock().unwrap();
+        while events
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > CONNECTION_FLAP_WINDOW)
+        {
+            events.pop_front();
         }
+        events.push_back(now);
+        events.len()
     };
+    if threshold == 0 || count <= threshold {
+        EventSender::new(app_handle.clone()).emit(payload);
+    } else if count == threshold + 1 {
+        warn!(
+            "OBS connection is unstable ({} events in {:?})",
+            count, CONNECTION_FLAP_WINDOW
+        );
+        let payload = ConnectionUnstablePayload {
+            events: count,
+            window_sec: CONNECTION_FLAP_WINDOW.as_secs(),
+        };
+        EventSender::new(app_handle.clone()).emit(payload);
+    } else {
+        debug!("Suppressed {} event while connection is unstable", E::NAME);
+    }
+}
+
+// 保存済みのパスワードを差し替えて再接続する（UDP受信とセッションはそのまま）
+// 空文字でパスワードなし
+#[tauri::command]
+async fn update_obs_password(
+    new_password: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let conn_info = state.obs_connection_info.lock().unwrap().clone();
+    let Some((host, port, _, secure)) = conn_info else {
+        return Err("OBS接続情報が見つかりません".to_string());
+    };
+    let password = (!new_password.is_empty()).then_some(new_password);
+
+    // 打ち間違いで切断されないように、接続できてから差し替える
+    let mut obs = new_obs(&state);
+    obs.connect(&host, port, password.as_deref(), secure)
+        .await
+        .map_err(|e| format!("新しいパスワードでOBSに接続できませんでした: {}", e))?;
+    {
+        let mut conn_info = state.obs_connection_info.lock().unwrap();
+        if let Some((_, _, stored, _)) = conn_info.as_mut() {
+            *stored = password;
+        }
+    }
+    remember_connection(&state);
+
+    let is_running = *state.is_system_running.lock().unwrap();
+    if is_running {
+        relink_connected_obs(&state, &app_handle, obs).await?;
+    } else {
+        // 動作していなければ接続できるかの確認だけ
+        obs.disconnect().await;
+    }
+    info!("OBS password updated");
+    Ok("新しいパスワードでOBSに接続しました".to_string())
+}
+
+#[tauri::command]
+async fn set_pending_trigger_policy(
+    policy: PendingTriggerPolicy,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mut pending_trigger_policy = state.pending_trigger_policy.write().unwrap();
+    *pending_trigger_policy = policy;
+    Ok(format!(
+        "切断中のトリガーの扱いを{:?}に設定しました",
+        policy
+    ))
+}
+
+#[tauri::command]
+async fn get_sleep_duration(state: tauri::State<'_, AppState>) -> Result<u64, String> {
+    let sleep_dur = state.sleep_duration_sec.read().unwrap();
+    Ok(*sleep_dur)
+}
+
+#[tauri::command]
+async fn set_sleep_duration(
+    duration: u64,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let clamped_duration = duration.max(1).min(30); // 1-30秒の範囲制限
+
+    {
+        let mut sleep_dur = state.sleep_duration_sec.write().unwrap();
+        *sleep_dur = clamped_duration;
+    }
+
+    Ok(format!(
+        "録画遅延時間を{}秒に設定しました",
+        clamped_duration
+    ))
+}
+
+#[tauri::command]
+async fn set_trigger_enabled(
+    cmd: String,
+    enabled: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mugi_cmd: MugiCmd = cmd
+        .parse()
+        .map_err(|_| format!("不明なコマンドです: {}", cmd))?;
+
+    {
+        let mut trigger_cmds = state.trigger_cmds.write().unwrap();
+        if enabled {
+            trigger_cmds.insert(mugi_cmd);
+        } else {
+            trigger_cmds.remove(&mugi_cmd);
+        }
+    }
+
+    let status = if enabled { "有効" } else { "無効" };
+    Ok(format!("{}を{}にしました", cmd, status))
+}
+
+// 設定画面の表の1行
+#[derive(Serialize, Deserialize)]
+struct TriggerTableEntry {
+    event: String,
+    enabled: bool,
+    // Noneならプロファイルやsleep_duration_secの遅延を使う
+    delay_ms: Option<u64>,
+    // Noneならシーンを切り替えない
+    scene: Option<String>,
+}
+
+// トリガー表で指定できる遅延の上限
+const MAX_TRIGGER_DELAY_MS: u64 = 30_000;
+
+#[tauri::command]
+async fn get_trigger_table(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<TriggerTableEntry>, String> {
+    let trigger_cmds = state.trigger_cmds.read().unwrap();
+    let delays = state.trigger_delays_ms.read().unwrap();
+    let scenes = state.trigger_scenes.read().unwrap();
+    Ok(MugiCmd::ALL
+        .into_iter()
+        .map(|cmd| TriggerTableEntry {
+            event: cmd.as_str().to_string(),
+            enabled: trigger_cmds.contains(&cmd),
+            delay_ms: delays.get(&cmd).copied(),
+            scene: scenes.get(&cmd).cloned(),
+        })
+        .collect())
+}
+
+// 全ての行を確認してから反映する（1つでも不正なら何も変えない）
+// 指定しなかったイベントはそのまま
+#[tauri::command]
+async fn set_trigger_table(
+    entries: Vec<TriggerTableEntry>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mut rows = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let cmd: MugiCmd = entry
+            .event
+            .parse()
+            .map_err(|_| format!("不明なコマンドです: {}", entry.event))?;
+        if let Some(delay_ms) = entry.delay_ms
+            && delay_ms > MAX_TRIGGER_DELAY_MS
+        {
+            return Err(format!(
+                "{}の遅延は{}ms以下にしてください",
+                entry.event, MAX_TRIGGER_DELAY_MS
+            ));
+        }
+        let scene = entry
+            .scene
+            .map(|scene| scene.trim().to_string())
+            .filter(|scene| !scene.is_empty());
+        rows.push((cmd, entry.enabled, entry.delay_ms, scene));
+    }
+    // シーンの指定があるときだけOBSに確認する
+    if rows.iter().any(|(_, _, _, scene)| scene.is_some()) {
+        let obs = connect_stored_obs(&state).await?;
+        for (_, _, _, scene) in &rows {
+            if let Some(scene) = scene
+                && !obs.scene_exists(scene).await?
+            {
+                return Err(format!("シーン{}が見つかりません", scene));
+            }
+        }
+    }
+
+    let count = rows.len();
+    {
+        let mut trigger_cmds = state.trigger_cmds.write().unwrap();
+        let mut delays = state.trigger_delays_ms.write().unwrap();
+        let mut scenes = state.trigger_scenes.write().unwrap();
+        for (cmd, enabled, delay_ms, scene) in rows {
+            if enabled {
+                trigger_cmds.insert(cmd);
+            } else {
+                trigger_cmds.remove(&cmd);
+            }
+            match delay_ms {
+                Some(delay_ms) => delays.insert(cmd, delay_ms),
+                None => delays.remove(&cmd),
+            };
+            match scene {
+                Some(scene) => scenes.insert(cmd, scene),
+                None => scenes.remove(&cmd),
+            };
+        }
+    }
+    Ok(format!("{}件のトリガー設定を反映しました", count))
+}
+
+#[derive(Serialize)]
+struct SupportedCommand {
+    cmd: MugiCmd,
+    // set_trigger_enabled等に渡す名前
+    token: &'static str,
+}
+
+#[tauri::command]
+async fn list_supported_commands() -> Result<Vec<SupportedCommand>, String> {
+    Ok(MugiCmd::ALL
+        .into_iter()
+        .map(|cmd| SupportedCommand {
+            cmd,
+            token: cmd.as_str(),
+        })
+        .collect())
+}
+
+// parse_cmdの結果（失敗したらどの段階で失敗したか）
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum CommandPreview {
+    Parsed {
+        cmd: MugiCmd,
+        token: &'static str,
+        periodic: bool,
+    },
+    // JSONとして読めない、cmdがないなど
+    InvalidJson {
+        error: String,
+        line: usize,
+        column: usize,
+    },
+    // cmdが対応していない名前
+    UnknownCommand {
+        cmd: String,
+        error: String,
+    },
+}
+
+// 動作中のシステムには流さず、パースした結果だけを返す
+#[tauri::command]
+async fn parse_command_preview(raw: String) -> Result<CommandPreview, String> {
+    let e = match mugi_schema::parse_cmd(&raw) {
+        Ok(cmd) => {
+            return Ok(CommandPreview::Parsed {
+                cmd,
+                token: cmd.as_str(),
+                periodic: cmd.is_periodic(),
+            });
+        }
+        Err(e) => e,
+    };
+    if let Some(json_error) = e.downcast_ref::<serde_json::Error>() {
+        return Ok(CommandPreview::InvalidJson {
+            error: json_error.to_string(),
+            line: json_error.line(),
+            column: json_error.column(),
+        });
+    }
+    // JSONとしては読めているので、cmdは文字列で入っている
+    let cmd = serde_json::from_str::<serde_json::Value>(&raw)
+        .ok()
+        .and_then(|value| Some(value.get("cmd")?.as_str()?.to_string()))
+        .unwrap_or_default();
+    Ok(CommandPreview::UnknownCommand {
+        cmd,
+        error: e.to_string(),
+    })
+}
+
+// Noneで解除
+#[tauri::command]
+async fn set_trigger_sequence(
+    pattern: Option<TriggerSequence>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let pattern = match pattern {
+        Some(mut pattern) => {
+            if pattern.steps.is_empty() {
+                return Err("コマンドを1つ以上指定してください".to_string());
+            }
+            pattern.within_sec = pattern.within_sec.clamp(1, 60); // 1-60秒の範囲制限
+            Some(pattern)
+        }
+        None => None,
+    };
+    let message = match &pattern {
+        Some(pattern) => format!(
+            "{}が{}秒以内に揃ったら保存します",
+            pattern.label(),
+            pattern.within_sec
+        ),
+        None => "トリガーの並びを解除しました".to_string(),
+    };
+    *state.trigger_sequence.write().unwrap() = pattern;
+    Ok(message)
+}
+
+#[tauri::command]
+async fn get_trigger_enabled(
+    cmd: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<bool, String> {
+    let mugi_cmd: MugiCmd = cmd
+        .parse()
+        .map_err(|_| format!("不明なコマンドです: {}", cmd))?;
+    let trigger_cmds = state.trigger_cmds.read().unwrap();
+    Ok(trigger_cmds.contains(&mugi_cmd))
+}
+
+#[tauri::command]
+async fn set_playback_audio_mode(
+    mode: PlaybackAudioMode,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mut audio_mode = state.playback_audio_mode.write().unwrap();
+    *audio_mode = mode;
+    Ok(format!("再生時の音声を{:?}に設定しました", mode))
+}
+
+#[derive(Serialize, Clone, Default)]
+struct TrimProgressPayload {
+    index: usize,
+    progress: f64,
+    finished: bool,
+    output: Option<PathBuf>,
+    error: Option<String>,
+}
+
+impl AppEvent for TrimProgressPayload {
+    const NAME: &'static str = "trim-progress";
+}
+
+fn emit_trim_progress(app_handle: &AppHandle, payload: TrimProgressPayload) {
+    EventSender::new(app_handle.clone()).emit(payload);
+}
+
+// 再生できるクリップか確認する（先頭フレームが読めるか）
+#[tauri::command]
+async fn verify_clip(path: String) -> Result<String, String> {
+    let path = PathBuf::from(path);
+    vlc_manager::validate_clip_path(&path)?;
+    ffmpeg::probe_first_frame(&path).await?;
+    Ok(format!("再生できます: {}", path.display()))
+}
+
+// index番目のクリップをstart_sec~end_secに切り詰めたコピーを作り、一覧を差し替える
+// 処理はバックグラウンドで行い、進捗はtrim-progressで通知
+#[tauri::command]
+async fn trim_clip(
+    index: usize,
+    start_sec: f64,
+    end_sec: f64,
+    keep_original: bool,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let input = {
+        let clips = state.clips.lock().unwrap();
+        match clips.get(index) {
+            Some(path) => path.clone(),
+            None => return Err(format!("{}番目のクリップが見つかりません", index)),
+        }
+    };
+    if start_sec < 0.0 || end_sec <= start_sec {
+        return Err("トリミング範囲が不正です".to_string());
+    }
+    let duration = ffmpeg::probe_duration(&input).await?;
+    if end_sec > duration {
+        return Err(format!(
+            "終了位置がクリップの長さ({:.1}秒)を超えています",
+            duration
+        ));
+    }
+
+    let output = ffmpeg::trimmed_path(&input);
+    let mut export_job = state.export_job.lock().unwrap();
+    if export_job
+        .as_ref()
+        .is_some_and(|job| !job.task.is_finished())
+    {
+        return Err("他のトリミングを実行中です".to_string());
+    }
+    let job_output = output.clone();
+    let task = tokio::spawn(async move {
+        let on_progress = |progress| {
+            emit_trim_progress(
+                &app_handle,
+                TrimProgressPayload {
+                    index,
+                    progress,
+                    ..Default::default()
+                },
+            )
+        };
+        // まずストリームコピーで試して、ダメなら再エンコード
+        let mut result =
+            ffmpeg::trim(&input, &output, start_sec, end_sec, false, on_progress).await;
+        if let Err(e) = &result {
+            info!("Stream copy failed, re-encoding: {}", e);
+            result = ffmpeg::trim(&input, &output, start_sec, end_sec, true, on_progress).await;
+        }
+
+        if let Err(e) = result {
+            error!("Failed to trim clip: {}", e);
+            let _ = std::fs::remove_file(&output);
+            emit_trim_progress(
+                &app_handle,
+                TrimProgressPayload {
+                    index,
+                    finished: true,
+                    error: Some(e),
+                    ..Default::default()
+                },
+            );
+            return;
+        }
+
+        // sidecarも引き継ぐ
+        let _ = std::fs::copy(
+            sidecar::sidecar_path(&input),
+            sidecar::sidecar_path(&output),
+        );
+        let state = app_handle.state::<AppState>();
+        if keep_original {
+            // 元のクリップは残して、切り出したものを別のクリップとして記録する
+            state.clips.lock().unwrap().push(output.clone());
+            track_created_clip(&state, output.clone());
+            add_session_clip(
+                &state,
+                SessionClip {
+                    path: output.clone(),
+                    kind: ClipKind::Video,
+                    event: None,
+                    triggered_at: unix_millis(),
+                    delay_ms: 0,
+                },
+            );
+        } else {
+            rename_tracked_clip(&state, &input, &output);
+            if let Err(e) = std::fs::remove_file(&input) {
+                error!("Failed to remove original clip: {}", e);
+            }
+            let _ = std::fs::remove_file(sidecar::sidecar_path(&input));
+        }
+
+        info!("Clip trimmed: {:?}", output);
+        emit_trim_progress(
+            &app_handle,
+            TrimProgressPayload {
+                index,
+                progress: 1.0,
+                finished: true,
+                output: Some(output),
+                ..Default::default()
+            },
+        );
+    });
+    *export_job = Some(ExportJob {
+        task,
+        index,
+        output: job_output,
+    });
+
+    Ok("トリミングを開始しました".to_string())
+}
+
+#[derive(Serialize, Clone)]
+struct ExportCancelledPayload {
+    index: usize,
+    output: PathBuf,
+}
+
+impl AppEvent for ExportCancelledPayload {
+    const NAME: &'static str = "export-cancelled";
+}
+
+// 実行中のトリミングを止めて、書きかけのファイルを消す
+#[tauri::command]
+async fn cancel_export(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let job = state.export_job.lock().unwrap().take();
+    let Some(job) = job.filter(|job| !job.task.is_finished()) else {
+        return Err("実行中のトリミングはありません".to_string());
+    };
+    job.task.abort();
+    if job.task.await.is_ok() {
+        // abortする前に終わっていたら出力は消さない
+        return Err("トリミングは既に完了しています".to_string());
+    }
+    if job.output.exists()
+        && let Err(e) = std::fs::remove_file(&job.output)
+    {
+        warn!("Failed to remove partial output: {}", e);
+    }
+
+    info!("Export cancelled: {:?}", job.output);
+    let payload = ExportCancelledPayload {
+        index: job.index,
+        output: job.output,
+    };
+    EventSender::new(app_handle.clone()).emit(payload);
+    Ok("トリミングを中止しました".to_string())
+}
+
+#[tauri::command]
+async fn set_capture_mode(
+    mode: CaptureMode,
+    timeout_sec: Option<u64>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    {
+        let mut capture_mode = state.capture_mode.write().unwrap();
+        *capture_mode = mode;
+    }
+    // 動作中に切り替えた場合はここで録画を始める
+    if mode == CaptureMode::Continuous
+        && let Some(obs) = current_obs(&state)
+    {
+        obs.set_recording().await?;
+    }
+    if let Some(timeout_sec) = timeout_sec {
+        let clamped_timeout = timeout_sec.clamp(1, 60); // 1-60秒の範囲制限
+        let mut next_event_timeout = state.next_event_timeout_sec.write().unwrap();
+        *next_event_timeout = clamped_timeout;
+    }
+    Ok(format!("キャプチャモードを{:?}に設定しました", mode))
+}
+
+// 0でまとめずに個別に保存する
+#[tauri::command]
+async fn set_combo_window(
+    window_sec: u64,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let clamped_window = window_sec.min(30); // 0-30秒の範囲制限
+    *state.combo_window_sec.write().unwrap() = clamped_window;
+    if clamped_window == 0 {
+        return Ok("セーブ→ゴールのまとめ保存を無効にしました".to_string());
+    }
+    Ok(format!(
+        "セーブ後{}秒以内のゴールを1つのクリップにまとめます",
+        clamped_window
+    ))
+}
+
+// 0で監視しない
+#[tauri::command]
+async fn set_heartbeat_timeout(
+    timeout_sec: u64,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let clamped_timeout = timeout_sec.min(300); // 0-300秒の範囲制限
+    *state.heartbeat_timeout_sec.write().unwrap() = clamped_timeout;
+    if clamped_timeout == 0 {
+        return Ok("ハートビートの監視を無効にしました".to_string());
+    }
+    Ok(format!(
+        "ハートビートが{}秒途切れたら保存します",
+        clamped_timeout
+    ))
+}
+
+#[tauri::command]
+async fn set_connection_flap_threshold(
+    threshold: u32,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let clamped_threshold = threshold.min(100); // 0-100回の範囲制限
+    *state.connection_flap_threshold.write().unwrap() = clamped_threshold;
+    if clamped_threshold == 0 {
+        return Ok("接続イベントのまとめを無効にしました".to_string());
+    }
+    Ok(format!(
+        "{}秒間に接続イベントが{}回を超えたら1つにまとめます",
+        CONNECTION_FLAP_WINDOW.as_secs(),
+        clamped_threshold
+    ))
+}
+
+#[tauri::command]
+async fn get_session_markers(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<SessionMarker>, String> {
+    let session = state.session.lock().unwrap();
+    Ok(session
+        .as_ref()
+        .map(|session| session.markers.clone())
+        .unwrap_or_default())
+}
+
+// 連続録画モードのトリガー。保存せずにマーカーだけ残す
+fn add_session_marker(state: &AppState, app_handle: &AppHandle, trigger: &Trigger) {
+    let mut label = format!("{:?}", trigger.cmd);
+    if let Some(clock) = trigger.tags.get("clock") {
+        label = format!("{label} ({clock})");
+    }
+    // 録画ファイルの中の位置にするため、録画の開始時刻を基準にする
+    let recording_started_at = current_obs(state).and_then(|obs| obs.recording_started_at());
+    let mut session = state.session.lock().unwrap();
+    let Some(session) = session.as_mut() else {
+        return;
+    };
+    let origin = recording_started_at.unwrap_or(session.started_at);
+    let marker = SessionMarker {
+        timestamp: trigger.timestamp,
+        offset_ms: trigger.timestamp.saturating_sub(origin),
+        event: trigger.cmd,
+        label,
+    };
+    info!("Session marker: {} at {}ms", marker.label, marker.offset_ms);
+    EventSender::new(app_handle.clone()).emit(marker.clone());
+    if let Err(e) = session.add_marker(marker) {
+        error!("{}", e);
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct SessionPurgedPayload {
+    removed: usize,
+}
+
+impl AppEvent for SessionPurgedPayload {
+    const NAME: &'static str = "session-purged";
+}
+
+// 現在のセッションでアプリが保存したクリップを全て消す（セッション自体は続ける）
+// 誤操作で消さないようにconfirmがtrueのときだけ実行する
+#[tauri::command]
+async fn purge_session_clips(
+    confirm: bool,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    if !confirm {
+        return Err("削除するにはconfirmをtrueにしてください".to_string());
+    }
+    let (paths, folder) = {
+        let mut session = state.session.lock().unwrap();
+        let Some(session) = session.as_mut() else {
+            return Err("セッションが開始されていません".to_string());
+        };
+        session.markers.clear();
+        let paths: Vec<PathBuf> = session.clips.drain(..).map(|clip| clip.path).collect();
+        (paths, session.folder())
+    };
+    state
+        .created_clips
+        .lock()
+        .unwrap()
+        .retain(|p| !paths.contains(p));
+    for path in &paths {
+        remove_clip_file(&state, path);
+    }
+    if folder.exists()
+        && let Err(e) = std::fs::remove_dir_all(&folder)
+    {
+        error!("Failed to remove session folder {:?}: {}", folder, e);
+    }
+
+    info!("Purged {} session clips", paths.len());
+    let payload = SessionPurgedPayload {
+        removed: paths.len(),
+    };
+    EventSender::new(app_handle.clone()).emit(payload);
+    Ok(format!(
+        "セッションのクリップを{}個削除しました",
+        paths.len()
+    ))
+}
+
+#[tauri::command]
+async fn get_session_manifest(
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<SessionManifest>, String> {
+    let session = state.session.lock().unwrap();
+    Ok(session.clone())
+}
+
+// 同名のプロファイルは上書き
+#[tauri::command]
+async fn save_profile(
+    name: String,
+    delays: DelayProfile,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    if name.trim().is_empty() {
+        return Err("プロファイル名が空です".to_string());
+    }
+    // set_sleep_durationと同じく1-30秒の範囲制限
+    let delays: DelayProfile = delays
+        .into_iter()
+        .map(|(cmd, sec)| (cmd, sec.clamp(1, 30)))
+        .collect();
+    let mut profiles = state.delay_profiles.write().unwrap();
+    profiles.insert(name.clone(), delays);
+    Ok(format!("プロファイル{}を保存しました", name))
+}
+
+// 空文字で解除
+#[tauri::command]
+async fn set_active_profile(
+    name: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mut active_profile = state.active_profile.write().unwrap();
+    if name.is_empty() {
+        *active_profile = None;
+        return Ok("プロファイルを解除しました".to_string());
+    }
+    if !state.delay_profiles.read().unwrap().contains_key(&name) {
+        return Err(format!("プロファイル{}が見つかりません", name));
+    }
+    *active_profile = Some(name.clone());
+    Ok(format!("プロファイルを{}に切り替えました", name))
+}
+
+// アクティブな遅延プロファイルにそのイベントの遅延があれば、postよりそちらを優先する
+#[tauri::command]
+async fn set_clip_profile(
+    cmd: String,
+    pre: u64,
+    post: u64,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mugi_cmd: MugiCmd = cmd
+        .parse()
+        .map_err(|_| format!("不明なコマンドです: {}", cmd))?;
+    let profile = ClipProfile {
+        pre_secs: pre.min(300),       // 0-300秒の範囲制限
+        post_secs: post.clamp(1, 30), // 1-30秒の範囲制限
+    };
+    state
+        .clip_profiles
+        .write()
+        .unwrap()
+        .insert(mugi_cmd, profile);
+    Ok(format!(
+        "{}のクリップを前{}秒・後{}秒にしました",
+        cmd, profile.pre_secs, profile.post_secs
+    ))
+}
+
+// pathがNoneか空ならOBSの設定のままにする
+// OBSの保存先から移すので、OBSと同じPCのフォルダを指定する（なければ保存時に作る）
+#[tauri::command]
+async fn set_trigger_output_folder(
+    cmd: String,
+    path: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mugi_cmd: MugiCmd = cmd
+        .parse()
+        .map_err(|_| format!("不明なコマンドです: {}", cmd))?;
+    let path = path.filter(|path| !path.trim().is_empty());
+    let mut folders = state.trigger_output_folders.write().unwrap();
+    match path {
+        Some(path) => {
+            folders.insert(mugi_cmd, PathBuf::from(&path));
+            Ok(format!("{}の保存先を{}にしました", cmd, path))
+        }
+        None => {
+            folders.remove(&mugi_cmd);
+            Ok(format!("{}の保存先をOBSの設定に戻しました", cmd))
+        }
+    }
+}
+
+// modeがNoneならスクリーンショットを撮らない
+#[tauri::command]
+async fn set_capture_screenshot_on(
+    cmd: String,
+    mode: Option<ScreenshotMode>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mugi_cmd: MugiCmd = cmd
+        .parse()
+        .map_err(|_| format!("不明なコマンドです: {}", cmd))?;
+    let mut screenshot_on = state.capture_screenshot_on.write().unwrap();
+    match mode {
+        Some(mode) => {
+            screenshot_on.insert(mugi_cmd, mode);
+            Ok(match mode {
+                ScreenshotMode::Instead => {
+                    format!("{}では動画の代わりにスクリーンショットを撮ります", cmd)
+                }
+                ScreenshotMode::Also => {
+                    format!("{}では動画と一緒にスクリーンショットを撮ります", cmd)
+                }
+            })
+        }
+        None => {
+            screenshot_on.remove(&mugi_cmd);
+            Ok(format!("{}のスクリーンショットを無効にしました", cmd))
+        }
+    }
+}
+
+#[tauri::command]
+async fn list_profiles(
+    state: tauri::State<'_, AppState>,
+) -> Result<HashMap<String, DelayProfile>, String> {
+    let profiles = state.delay_profiles.read().unwrap();
+    Ok(profiles.clone())
+}
+
+// 受信したUDPコマンドを相対時刻付きで記録する
+#[tauri::command]
+async fn start_recording_triggers(
+    path: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mut trigger_recording = state.trigger_recording.lock().unwrap();
+    if trigger_recording.is_some() {
+        return Err("既に記録中です".to_string());
+    }
+    *trigger_recording = Some(TriggerRecording::new(PathBuf::from(&path)));
+    info!("Start recording triggers to {}", path);
+    Ok(format!("トリガーの記録を開始しました: {}", path))
+}
+
+#[tauri::command]
+async fn stop_recording_triggers(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let recording = state.trigger_recording.lock().unwrap().take();
+    let Some(recording) = recording else {
+        return Err("記録していません".to_string());
+    };
+    let count = recording.save()?;
+    Ok(format!("{}件のコマンドを記録しました", count))
+}
+
+// 記録したコマンドを元のタイミングで動作中のシステムに流し込む
+#[tauri::command]
+async fn replay_triggers(
+    path: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let tx = state.udp_msg_tx.lock().unwrap().clone();
+    let Some(tx) = tx else {
+        return Err("システムは動作していません".to_string());
+    };
+    let messages = recording::load(Path::new(&path))?;
+    let count = messages.len();
+    tokio::spawn(recording::replay(messages, tx));
+    Ok(format!("{}件のコマンドを再生します", count))
+}
+
+// 台本のコマンドを動作中のシステムに流し込む
+// real_savesを指定しなければモックOBSで動いているときだけ流す（本物のOBSには保存しない）
+#[tauri::command]
+async fn run_demo(
+    scenario: DemoScenario,
+    real_saves: Option<bool>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let tx = state.udp_msg_tx.lock().unwrap().clone();
+    let Some(tx) = tx else {
+        return Err("システムは動作していません".to_string());
+    };
+    if !real_saves.unwrap_or(false) && state.mock_obs.read().unwrap().is_none() {
+        return Err(
+            "モックOBSで開始するか、実際に保存する場合はreal_savesを指定してください".to_string(),
+        );
+    }
+    let messages = scenario.messages();
+    let count = messages.len();
+    info!("Start demo {:?}", scenario);
+    tokio::spawn(recording::replay(messages, tx));
+    Ok(format!(
+        "デモ({:?})の{}件のコマンドを流します",
+        scenario, count
+    ))
+}
+
+// actionがNoneなら設定を消して通常の動作に戻す
+#[tauri::command]
+async fn set_source_action(
+    ip: String,
+    action: Option<SourceAction>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let ip: IpAddr = ip
+        .trim()
+        .parse()
+        .map_err(|e| format!("Invalid IP address {}: {}", ip, e))?;
+    let mut source_actions = state.source_actions.write().unwrap();
+    match action {
+        Some(action) => {
+            let message = format!("{}からのトリガーを{:?}に設定しました", ip, action);
+            source_actions.insert(ip, action);
+            Ok(message)
+        }
+        None => {
+            source_actions.remove(&ip);
+            Ok(format!("{}からのトリガーを通常の動作に戻しました", ip))
+        }
+    }
+}
+
+// 動作中なら即座に待ち受けを開始する
+#[tauri::command]
+async fn add_udp_listener(
+    addr: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let addr = addr.trim().to_string();
+    addr.parse::<std::net::SocketAddr>()
+        .map_err(|e| format!("Invalid address {}: {}", addr, e))?;
+    {
+        let mut udp_bind_addrs = state.udp_bind_addrs.write().unwrap();
+        if !udp_bind_addrs.contains(&addr) {
+            udp_bind_addrs.push(addr.clone());
+        }
+    }
+    let is_running = *state.is_system_running.lock().unwrap();
+    if is_running {
+        spawn_udp_listener(&state, addr.clone(), &app_handle)?;
+    }
+    Ok(format!("{}を待ち受けに追加しました", addr))
+}
+
+#[tauri::command]
+async fn remove_udp_listener(
+    addr: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let addr = addr.trim().to_string();
+    state.udp_bind_addrs.write().unwrap().retain(|a| *a != addr);
+    // AbortOnDropなので外せば止まる
+    state.udp_listeners.lock().unwrap().remove(&addr);
+    Ok(format!("{}の待ち受けを停止しました", addr))
+}
+
+#[derive(Serialize)]
+struct PluginTarget {
+    interface: String,
+    address: IpAddr,
+    // プラグインに設定する送信先 (例: 192.168.1.10:12344)
+    target: String,
+}
+
+// プラグインの送信先に使えるこのPCのアドレス（ループバックとIPv6のリンクローカルは除く）
+// 待ち受けアドレスが0.0.0.0/[::]なら同じ種類の全アドレスを、それ以外ならそのアドレスだけを返す
+#[tauri::command]
+async fn get_local_addresses(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<PluginTarget>, String> {
+    let interfaces =
+        if_addrs::get_if_addrs().map_err(|e| format!("Failed to list network interfaces: {e}"))?;
+    let interfaces: Vec<_> = interfaces
+        .into_iter()
+        .filter(|interface| !interface.is_loopback())
+        .filter(|interface| match interface.ip() {
+            IpAddr::V4(_) => true,
+            IpAddr::V6(ip) => !ip.is_unicast_link_local(),
+        })
+        .collect();
+    let udp_bind_addrs = state.udp_bind_addrs.read().unwrap().clone();
+    let mut targets = Vec::new();
+    for addr in udp_bind_addrs {
+        let Ok(bind) = addr.parse::<std::net::SocketAddr>() else {
+            warn!("Invalid UDP bind address: {}", addr);
+            continue;
+        };
+        for interface in &interfaces {
+            let ip = interface.ip();
+            let matches = if bind.ip().is_unspecified() {
+                ip.is_ipv4() == bind.is_ipv4()
+            } else {
+                ip == bind.ip()
+            };
+            if matches {
+                targets.push(PluginTarget {
+                    interface: interface.name.clone(),
+                    address: ip,
+                    target: std::net::SocketAddr::new(ip, bind.port()).to_string(),
+                });
+            }
+        }
+    }
+    Ok(targets)
+}
+
+// 新しい順ではなく古い順で返す
+#[tauri::command]
+async fn get_recent_logs(
+    count: usize,
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<String>, String> {
+    let recent_logs = state.recent_logs.lock().unwrap();
+    let start = recent_logs.len().saturating_sub(count);
+    Ok(recent_logs.iter().skip(start).cloned().collect())
+}
+
+// 動作中のシステムは止めない（UDPの待ち受けアドレスは次回起動から反映）
+#[tauri::command]
+async fn reset_to_defaults(state: tauri::State<'_, AppState>) -> Result<AppConfig, String> {
+    // 保存した起動時の接続待ちも消さないと、次回の起動で戻ってしまう
+    let mut prefs = preferences::load()?;
+    if prefs.startup_delay_sec.take().is_some() {
+        preferences::save(&prefs)?;
+    }
+    state.reset_config();
+    info!("Config reset to defaults");
+    Ok(state.config())
+}
+
+// システム停止中はNone
+#[tauri::command]
+async fn get_session_stats(
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<SessionStats>, String> {
+    let counter = state.session_counter.lock().unwrap();
+    Ok(counter.as_ref().map(SessionCounter::stats))
+}
+
+#[tauri::command]
+async fn get_trigger_latencies(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<TriggerLatency>, String> {
+    let latencies = state.trigger_latencies.lock().unwrap();
+    Ok(latencies.iter().cloned().collect())
+}
+
+// 履歴が空でもヘッダーだけ書き出す
+#[tauri::command]
+async fn export_trigger_history(
+    path: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mut csv = String::from("timestamp,command,source,metadata\n");
+    let count = {
+        let history = state.trigger_history.lock().unwrap();
+        for entry in history.iter() {
+            // メタデータはkey=valueを;でつなぐ（順序を揃える）
+            let mut tags: Vec<String> = entry
+                .tags
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect();
+            tags.sort();
+            let fields = [
+                entry.timestamp.to_string(),
+                format!("{:?}", entry.cmd),
+                entry.source.map(|ip| ip.to_string()).unwrap_or_default(),
+                tags.join(";"),
+            ];
+            let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&row.join(","));
+            csv.push('\n');
+        }
+        history.len()
+    };
+    std::fs::write(&path, csv).map_err(|e| format!("Failed to write trigger history: {e}"))?;
+    if count == 0 {
+        return Ok("トリガーの履歴がありません（ヘッダーのみ書き出しました）".to_string());
+    }
+    Ok(format!("{}件のトリガーを書き出しました", count))
+}
+
+// ,や"、改行を含む値は"で囲む
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[derive(Serialize)]
+struct AdaptiveTiming {
+    enabled: bool,
+    // 保持しているレイテンシの平均（計測がなければNone）
+    average_latency_ms: Option<u64>,
+    override_ms: Option<u64>,
+    // 実際に待ち時間から引く値
+    compensation_ms: u64,
+}
+
+fn average_latency_ms(state: &AppState) -> Option<u64> {
+    let latencies = state.trigger_latencies.lock().unwrap();
+    if latencies.is_empty() {
+        return None;
+    }
+    let total: u64 = latencies.iter().map(|l| l.latency_ms).sum();
+    Some(total / latencies.len() as u64)
+}
+
+// 無効なら0
+fn latency_compensation(state: &AppState) -> std::time::Duration {
+    if !*state.adaptive_timing.read().unwrap() {
+        return std::time::Duration::ZERO;
+    }
+    let override_ms = *state.latency_override_ms.read().unwrap();
+    let ms = override_ms.or_else(|| average_latency_ms(state));
+    std::time::Duration::from_millis(ms.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn get_adaptive_timing(state: tauri::State<'_, AppState>) -> Result<AdaptiveTiming, String> {
+    Ok(AdaptiveTiming {
+        enabled: *state.adaptive_timing.read().unwrap(),
+        average_latency_ms: average_latency_ms(&state),
+        override_ms: *state.latency_override_ms.read().unwrap(),
+        compensation_ms: latency_compensation(&state).as_millis() as u64,
+    })
+}
+
+#[tauri::command]
+async fn set_adaptive_timing(
+    enabled: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    *state.adaptive_timing.write().unwrap() = enabled;
+    let status = if enabled { "有効" } else { "無効" };
+    Ok(format!("保存遅延の自動補正を{}にしました", status))
+}
+
+// Noneで計測した平均に戻す
+#[tauri::command]
+async fn set_latency_override(
+    override_ms: Option<u64>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let clamped_override = override_ms.map(|ms| ms.min(5000)); // 0-5秒の範囲制限
+    *state.latency_override_ms.write().unwrap() = clamped_override;
+    Ok(match clamped_override {
+        Some(ms) => format!("保存遅延の補正を{}msに固定しました", ms),
+        None => "保存遅延の補正を計測した平均に戻しました".to_string(),
+    })
+}
+
+// 空文字で解除
+#[tauri::command]
+async fn set_webhook_url(url: String, state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let url = url.trim();
+    let new_url = if url.is_empty() {
+        None
+    } else {
+        webhook::validate_url(url)?;
+        Some(url.to_string())
+    };
+
+    let message = match &new_url {
+        Some(url) => format!("Webhook URLを{}に設定しました", url),
+        None => "Webhookを解除しました".to_string(),
+    };
+    {
+        let mut webhook_url = state.webhook_url.write().unwrap();
+        *webhook_url = new_url;
+    }
+    Ok(message)
+}
+
+// モック・タイムアウト・トレースの設定を反映したOBSを作る（まだ接続しない）
+fn new_obs(state: &AppState) -> obs::Obs {
+    let mut obs = match state.mock_obs.read().unwrap().as_ref() {
+        Some(mock) => obs::Obs::new_mock(mock.clone()),
+        None => obs::Obs::new(),
+    };
+    obs.set_request_timeout(state.obs_request_timeout_sec.clone());
+    obs.set_trace(state.obs_trace.clone());
+    obs
+}
+
+// 接続中のOBSにもすぐ反映される
+#[tauri::command]
+async fn set_obs_request_timeout(
+    timeout_sec: u64,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let clamped_timeout = timeout_sec.clamp(1, 60); // 1-60秒の範囲制限
+    {
+        let mut obs_request_timeout = state.obs_request_timeout_sec.write().unwrap();
+        *obs_request_timeout = clamped_timeout;
+    }
+    Ok(format!(
+        "OBSリクエストのタイムアウトを{}秒に設定しました",
+        clamped_timeout
+    ))
+}
+
+// 接続中のOBSにもすぐ反映される（パスワードなどは伏せて出力する）
+#[tauri::command]
+async fn set_obs_trace(enabled: bool, state: tauri::State<'_, AppState>) -> Result<String, String> {
+    {
+        let mut obs_trace = state.obs_trace.write().unwrap();
+        *obs_trace = enabled;
+    }
+
+    let status = if enabled { "有効" } else { "無効" };
+    Ok(format!("OBS通信のトレースログを{}にしました", status))
+}
+
+// 動作中は切り替えられない
+#[tauri::command]
+async fn set_mock_obs(enabled: bool, state: tauri::State<'_, AppState>) -> Result<String, String> {
+    if *state.is_system_running.lock().unwrap() {
+        return Err("システム動作中は切り替えられません".to_string());
+    }
+    {
+        let mut mock_obs = state.mock_obs.write().unwrap();
+        *mock_obs = enabled.then(|| Arc::new(MockObs::new()));
+    }
+
+    let status = if enabled { "有効" } else { "無効" };
+    Ok(format!("モックOBSを{}にしました", status))
+}
+
+// 保存済みの接続情報でOBSに接続する
+async fn connect_stored_obs(state: &AppState) -> Result<obs::Obs, String> {
+    // OBS接続情報を取得
+    let (host, port, password, secure) = {
+        let conn_info = state.obs_connection_info.lock().unwrap();
+        match conn_info.as_ref() {
+            Some((host, port, password, secure)) => {
+                (host.clone(), *port, password.clone(), *secure)
+            }
+            None => return Err("OBS接続情報が見つかりません".to_string()),
+        }
+    };
+
+    // OBS接続を作成
+    let mut obs = new_obs(state);
+    let password_ref = password.as_deref();
+    obs.connect(&host, port, password_ref, secure)
+        .await
+        .map_err(|e| format!("Failed to connect to OBS: {}", e))?;
+    Ok(obs)
+}
+
+#[derive(Serialize, Clone)]
+struct ReplaySavedPayload {
+    path: PathBuf,
+    label: Option<String>,
+}
+
+impl AppEvent for ReplaySavedPayload {
+    const NAME: &'static str = "replay-saved";
+}
+
+// UDPトリガーを待たずに手動でリプレイを保存
+#[tauri::command]
+async fn save_replay_now(
+    label: Option<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let obs = connect_stored_obs(&state).await?;
+    // トリガーの保存先に移されないように、トリガーの保存と重ねない
+    let save_guard = state.save_lock.lock().await;
+    let path = obs
+        .save_replay_buffer_with_path()
+        .await
+        .map_err(|e| format!("Failed to save replay buffer: {}", e))?;
+    drop(save_guard);
+    info!("Manual replay saved: {:?}", path);
+    record_manual_clip(&state, &app_handle, path, label);
+
+    Ok("リプレイを保存しました".to_string())
+}
+
+// 計測の合間に待つ時間（本物のトリガーの保存を割り込ませる）
+const BENCHMARK_SLEEP: std::time::Duration = std::time::Duration::from_millis(500);
+
+#[derive(Serialize)]
+struct SaveBenchmark {
+    iterations: u32,
+    min_ms: u64,
+    max_ms: u64,
+    avg_ms: u64,
+}
+
+// OBSの保存リクエストにかかる時間を測る（保存したクリップは消す）
+#[tauri::command]
+async fn benchmark_save(
+    iterations: u32,
+    state: tauri::State<'_, AppState>,
+) -> Result<SaveBenchmark, String> {
+    let iterations = iterations.clamp(1, 20); // 1-20回の範囲制限
+    let obs = connect_stored_obs(&state).await?;
+    let mut latencies = Vec::new();
+    for _ in 0..iterations {
+        tokio::time::sleep(BENCHMARK_SLEEP).await;
+        let (path, discarded) = {
+            let _save_guard = state.save_lock.lock().await;
+            // 動作中ならリスナーに消してもらい、再生リストやフロントエンドに流さない
+            let discard = if *state.is_system_running.lock().unwrap() {
+                let (tx, rx) = oneshot::channel();
+                *state.replay_redirect.lock().unwrap() = Some((NextReplay::Discard, tx));
+                Some(rx)
+            } else {
+                None
+            };
+            let before = obs.get_last_replay().await;
+            let started = std::time::Instant::now();
+            let saved = match obs.save_replay_buffer().await {
+                Ok(()) => {
+                    latencies.push(started.elapsed().as_millis() as u64);
+                    // 書き出しが終わってから次の保存をする
+                    obs.wait_for_new_replay(before).await
+                }
+                Err(e) => Err(format!("Failed to save replay buffer: {}", e)),
+            };
+            let discarded = match (&saved, discard) {
+                (Ok(_), Some(rx)) => {
+                    matches!(
+                        tokio::time::timeout(REPLAY_REDIRECT_TIMEOUT, rx).await,
+                        Ok(Ok(_))
+                    )
+                }
+                _ => false,
+            };
+            state.replay_redirect.lock().unwrap().take();
+            (saved?, discarded)
+        };
+        if !discarded
+            && state.mock_obs.read().unwrap().is_none()
+            && let Err(e) = std::fs::remove_file(&path)
+        {
+            warn!("Failed to remove benchmark clip {:?}: {}", path, e);
+        }
+    }
+    let benchmark = SaveBenchmark {
+        iterations,
+        min_ms: latencies.iter().copied().min().unwrap_or_default(),
+        max_ms: latencies.iter().copied().max().unwrap_or_default(),
+        avg_ms: latencies.iter().sum::<u64>() / latencies.len() as u64,
+    };
+    info!(
+        "Save benchmark: min {}ms, max {}ms, avg {}ms ({} iterations)",
+        benchmark.min_ms, benchmark.max_ms, benchmark.avg_ms, iterations
+    );
+    Ok(benchmark)
+}
+
+#[tauri::command]
+async fn get_filename_formatting(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let obs = connect_stored_obs(&state).await?;
+    obs.get_filename_formatting().await
+}
+
+#[derive(Serialize)]
+struct EffectiveOutputInfo {
+    #[serde(flatten)]
+    replay: obs::ReplayOutputInfo,
+    // 書式の変数(%CCYYなど)は展開しない
+    path_pattern: PathBuf,
+    // OBSが同じPCで動いている場合のみ意味がある
+    writable: bool,
+}
+
+// OBSがリプレイを書き出すパスの見込み
+#[tauri::command]
+async fn get_effective_output_info(
+    state: tauri::State<'_, AppState>,
+) -> Result<EffectiveOutputInfo, String> {
+    let obs = connect_stored_obs(&state).await?;
+    let replay = obs.get_replay_output_info().await?;
+    let path_pattern = replay.directory.join(replay.filename_pattern());
+    let writable = check_dir_writable(&replay.directory).is_ok();
+    Ok(EffectiveOutputInfo {
+        replay,
+        path_pattern,
+        writable,
+    })
+}
+
+// OBSの保存先に書き込めるか確認する（OBSが同じPCで動いている前提）
+// 書き込めないとOBSは保存に失敗し、クリップが黙って増えなくなる
+#[tauri::command]
+async fn check_output_writable(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let obs = connect_stored_obs(&state).await?;
+    let directory = obs.get_replay_output_info().await?.directory;
+    check_dir_writable(&directory)?;
+    Ok(format!("{}に書き込めます", directory.display()))
+}
+
+// 入力されたフォルダを確認して正規化したパスを返す
+#[tauri::command]
+async fn normalize_directory(path: String) -> Result<PathBuf, String> {
+    normalize_directory_path(&path)
+}
+
+// ~を展開して絶対パスにし、存在して書き込めるか確認する
+fn normalize_directory_path(path: &str) -> Result<PathBuf, String> {
+    let path = path.trim();
+    if path.is_empty() {
+        return Err("フォルダが指定されていません".to_string());
+    }
+    let expanded = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            let home = std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .ok_or("ホームフォルダが見つかりません")?;
+            PathBuf::from(home).join(rest.trim_start_matches(['/', '\\']))
+        }
+        _ => PathBuf::from(path),
+    };
+    // Windowsでも\\?\の付かない普通のパスにする（OBSやVLCに渡すため）
+    let canonical = dunce::canonicalize(&expanded)
+        .map_err(|e| format!("フォルダが見つかりません: {} ({})", expanded.display(), e))?;
+    if !canonical.is_dir() {
+        return Err(format!("フォルダではありません: {}", canonical.display()));
+    }
+    check_dir_writable(&canonical)?;
+    Ok(canonical)
+}
+
+// 実際に一時ファイルを作って確認する
+fn check_dir_writable(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(format!(".rl_replay_write_test_{}", unix_millis()));
+    std::fs::write(&probe, b"")
+        .map_err(|e| format!("フォルダに書き込めません: {} ({})", dir.display(), e))?;
+    if let Err(e) = std::fs::remove_file(&probe) {
+        warn!("Failed to remove {:?}: {}", probe, e);
+    }
+    Ok(())
+}
+
+// OBSの録画形式(RecFormat2)として指定できる値
+const REPLAY_OUTPUT_FORMATS: &[&str] = &[
+    "mkv",
+    "mp4",
+    "mov",
+    "flv",
+    "mpegts",
+    "fragmented_mp4",
+    "fragmented_mov",
+    "hybrid_mp4",
+];
+
+#[derive(Serialize)]
+struct ReplayOutputFormat {
+    format: String,
+    // 途中で止まると壊れる形式なら注意書き
+    note: Option<String>,
+}
+
+impl ReplayOutputFormat {
+    fn new(format: String) -> Self {
+        let note = matches!(format.as_str(), "mp4" | "mov").then(|| {
+            format!(
+                "{}はOBSが途中で止まるとファイルが壊れて再生できなくなります。mkvかfragmented_mp4をおすすめします",
+                format
+            )
+        });
+        Self { format, note }
+    }
+}
+
+#[tauri::command]
+async fn get_replay_output_format(
+    state: tauri::State<'_, AppState>,
+) -> Result<ReplayOutputFormat, String> {
+    let obs = connect_stored_obs(&state).await?;
+    Ok(ReplayOutputFormat::new(
+        obs.get_replay_output_format().await?,
+    ))
+}
+
+// 動作中のリプレイバッファには次に開始したときから反映される
+#[tauri::command]
+async fn set_replay_output_format(
+    format: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<ReplayOutputFormat, String> {
+    let format = format.trim();
+    if !REPLAY_OUTPUT_FORMATS.contains(&format) {
+        return Err(format!(
+            "対応していない形式です: {} ({}のいずれかを指定してください)",
+            format,
+            REPLAY_OUTPUT_FORMATS.join(", ")
+        ));
+    }
+    let obs = connect_stored_obs(&state).await?;
+    obs.set_replay_output_format(format).await?;
+    Ok(ReplayOutputFormat::new(
+        obs.get_replay_output_format().await?,
+    ))
+}
+
+// OBS側のファイル名の書式を変える（/はOBSがサブフォルダとして扱う）
+#[tauri::command]
+async fn set_filename_formatting(
+    format: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    const ILLEGAL_CHARS: &[char] = &['<', '>', ':', '"', '\\', '|', '?', '*'];
+    let format = format.trim();
+    if format.is_empty() {
+        return Err("ファイル名の書式が空です".to_string());
+    }
+    if let Some(c) = format
+        .chars()
+        .find(|c| ILLEGAL_CHARS.contains(c) || c.is_control())
+    {
+        return Err(format!("ファイル名に使えない文字が含まれています: {:?}", c));
+    }
+    let obs = connect_stored_obs(&state).await?;
+    obs.set_filename_formatting(format).await?;
+    Ok(format!("ファイル名の書式を{}に設定しました", format))
+}
+
+#[tauri::command]
+async fn get_clip_tags(
+    index: usize,
+    state: tauri::State<'_, AppState>,
+) -> Result<HashMap<String, String>, String> {
+    let path = clip_at(&state, index)?;
+    Ok(sidecar::read_sidecar(&path)?.tags)
+}
+
+// タグを丸ごと置き換える（event等の他の項目はそのまま）
+#[tauri::command]
+async fn set_clip_tags(
+    index: usize,
+    tags: HashMap<String, String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let path = clip_at(&state, index)?;
+    let mut sidecar = sidecar::read_sidecar(&path)?;
+    sidecar.tags = tags;
+    sidecar::write_sidecar(&path, &sidecar)?;
+    Ok(format!("{}番目のクリップのタグを更新しました", index))
+}
+
+// position_msはクリップ先頭からの山場の位置
+// クリップの終端は保存した時点なので、トリガーから山場までの時間を逆算できる
+#[tauri::command]
+async fn mark_highlight_peak(
+    index: usize,
+    position_ms: u64,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let path = clip_at(&state, index)?;
+    let sidecar = sidecar::read_sidecar(&path)?;
+    let (Some(_), Some(delay_ms)) = (sidecar.event, sidecar.delay_ms) else {
+        return Err(format!(
+            "{}番目のクリップはトリガーで保存したクリップではありません",
+            index
+        ));
+    };
+    let duration_ms = (ffmpeg::probe_duration(&path).await? * 1000.0) as u64;
+    if position_ms > duration_ms {
+        return Err(format!(
+            "位置がクリップの長さ({}ms)を超えています",
+            duration_ms
+        ));
+    }
+    let offset_ms = delay_ms as i64 - (duration_ms - position_ms) as i64;
+    let ema = {
+        let mut peak_offset = state.peak_offset.lock().unwrap();
+        peak_offset.push(offset_ms as f64);
+        *peak_offset
+    };
+    info!(
+        "Highlight peak at {}ms from trigger (EMA {:.0}ms, {} samples)",
+        offset_ms, ema.offset_ms, ema.samples
+    );
+    Ok(format!(
+        "山場はトリガーから{:.1}秒の位置です",
+        offset_ms as f64 / 1000.0
+    ))
+}
+
+// 設定は変えずに提案だけ返す（山場を1つも付けていなければNone）
+#[tauri::command]
+async fn get_suggested_delay(
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<SuggestedDelay>, String> {
+    let ema = *state.peak_offset.lock().unwrap();
+    if ema.samples == 0 {
+        return Ok(None);
+    }
+    let delay_sec = (ema.offset_ms / 1000.0).round().clamp(1.0, 30.0) as u64; // 1-30秒の範囲制限
+    Ok(Some(SuggestedDelay {
+        delay_sec,
+        offset_ms: ema.offset_ms.round() as i64,
+        samples: ema.samples,
+        current_delay_sec: *state.sleep_duration_sec.read().unwrap(),
+    }))
+}
+
+fn clip_at(state: &AppState, index: usize) -> Result<PathBuf, String> {
+    let clips = state.clips.lock().unwrap();
+    clips
+        .get(index)
+        .cloned()
+        .ok_or_else(|| format!("{}番目のクリップが見つかりません", index))
+}
+
+// 手動で保存したクリップを記録して通知する
+fn record_manual_clip(
+    state: &AppState,
+    app_handle: &AppHandle,
+    path: PathBuf,
+    label: Option<String>,
+) {
+    track_created_clip(state, path.clone());
+    add_session_clip(
+        state,
+        SessionClip {
+            path: path.clone(),
+            kind: ClipKind::Video,
+            event: None,
+            triggered_at: unix_millis(),
+            delay_ms: 0,
+        },
+    );
+
+    let sidecar = ClipSidecar {
+        label: label.clone(),
+        timestamp: Some(unix_millis()),
+        delay_ms: Some(0),
+        ..Default::default()
+    };
+    if let Err(e) = sidecar::write_sidecar(&path, &sidecar) {
+        error!("{}", e);
+    }
+
+    let payload = ReplaySavedPayload { path, label };
+    EventSender::new(app_handle.clone()).emit(payload);
+}
+
+// リプレイバッファ全体を保存して、最後のlength_sec秒だけを残す
+// バッファの長さは変えない（縮めると溜まっていた映像が消え、伸ばしても過去の分は増えない）
+#[tauri::command]
+async fn capture_clip(
+    length_sec: u64,
+    label: Option<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    if length_sec == 0 {
+        return Err("長さは1秒以上にしてください".to_string());
+    }
+    let Some(_capturing) = BusyGuard::acquire(&state.is_capturing_clip) else {
+        return Err("他のキャプチャを実行中です".to_string());
+    };
+    let path = capture_clip_with_length(&state, length_sec).await?;
+    info!("Captured {}s clip: {:?}", length_sec, path);
+    record_manual_clip(&state, &app_handle, path.clone(), label);
+    Ok(format!(
+        "{}秒のクリップを保存しました: {}",
+        length_sec,
+        path.display()
+    ))
+}
+
+async fn capture_clip_with_length(state: &AppState, length_sec: u64) -> Result<PathBuf, String> {
+    let obs = connect_stored_obs(state).await?;
+    let buffer_sec = obs.get_replay_buffer_length().await?;
+    if length_sec > buffer_sec {
+        return Err(format!(
+            "リプレイバッファ（{}秒）より長くは保存できません",
+            buffer_sec
+        ));
+    }
+    // トリガーの保存先に移されないように、トリガーの保存と重ねない
+    let save_guard = state.save_lock.lock().await;
+    let path = obs
+        .save_replay_buffer_with_path()
+        .await
+        .map_err(|e| format!("Failed to save replay buffer: {}", e))?;
+    drop(save_guard);
+    // モックのクリップは実在しないので切らない
+    if state.mock_obs.read().unwrap().is_none()
+        && let Err(e) = trim_clip_head(&path, std::time::Duration::from_secs(length_sec)).await
+    {
+        // 保存はできているので、切らずに残す
+        warn!("Failed to trim {:?} to {}s: {}", path, length_sec, e);
+    }
+    Ok(path)
+}
+
+#[derive(Serialize, Clone)]
+struct PlaybackFinishedPayload;
+
+impl AppEvent for PlaybackFinishedPayload {
+    const NAME: &'static str = "playback-finished";
+}
+
+// 再生終了(stop_playback含む)を待って、音声設定とシーンを元に戻す
+fn spawn_playback_watcher(
+    obs: obs::Obs,
+    sources: Vec<&'static str>,
+    // sourcesと同じ順
+    deadlines: Vec<PlaybackDeadline>,
+    freeze_intro: std::time::Duration,
+    prev_audio: Option<AudioState>,
+    original_scene: Option<String>,
+    transition: Option<SceneTransition>,
+    hide_when_idle: bool,
+    app_handle: AppHandle,
+) {
+    tokio::spawn(async move {
+        if !freeze_intro.is_zero() {
+            VlcManager::freeze_intro(&obs, &sources, freeze_intro).await;
+        }
+        // 同時に再生しているので、全てのソースを並行して待つ
+        // 片方の確認に失敗しても、もう片方は期限まで待ってから戻す
+        let waits = sources
+            .iter()
+            .zip(&deadlines)
+            .map(|(source, deadline)| obs.wait_vlc_playback_end(source, deadline));
+        for result in futures_util::future::join_all(waits).await {
+            if let Err(e) = result {
+                error!("{}", e);
+            }
+        }
+        // シーンを戻す前に、表示したシーンで隠す
+        if hide_when_idle {
+            for source in &sources {
+                if let Err(e) = obs.hide_vlc_source(source).await {
+                    warn!("{}", e);
+                }
+            }
+        }
+        if let Some(prev) = prev_audio {
+            obs.restore_audio_state(prev).await;
+        }
+        if let Some(scene) = original_scene {
+            // 再生中に元のシーンが消されていたら戻さない
+            match obs.scene_exists(&scene).await {
+                Ok(true) => {
+                    if let Err(e) = obs
+                        .set_current_scene_with_transition(&scene, transition.as_ref())
+                        .await
+                    {
+                        error!("{}", e);
+                    }
+                }
+                Ok(false) => warn!("Original scene {} was removed, skip restoring", scene),
+                Err(e) => error!("{}", e),
+            }
+        }
+        EventSender::new(app_handle.clone()).emit(PlaybackFinishedPayload);
+    });
+}
+
+// ループ中はstop_playbackで止めるまで再生し続ける
+#[tauri::command]
+async fn set_playback_loop(
+    enabled: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    {
+        let mut playback_loop = state.playback_loop.write().unwrap();
+        *playback_loop = enabled;
+    }
+
+    let status = if enabled { "有効" } else { "無効" };
+    Ok(format!("ハイライトのループ再生を{}にしました", status))
+}
+
+// 0で止めずに再生する
+#[tauri::command]
+async fn set_playback_freeze_intro(
+    ms: u64,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let clamped_ms = ms.min(10000); // 0-10秒の範囲制限
+    *state.playback_freeze_intro_ms.write().unwrap() = clamped_ms;
+    if clamped_ms == 0 {
+        return Ok("再生開始時の静止を無効にしました".to_string());
+    }
+    Ok(format!(
+        "再生開始時に最初のフレームで{}ms止めます",
+        clamped_ms
+    ))
+}
+
+#[tauri::command]
+async fn set_hide_when_idle(
+    enabled: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    {
+        let mut hide_when_idle = state.hide_when_idle.write().unwrap();
+        *hide_when_idle = enabled;
+    }
+
+    let status = if enabled { "有効" } else { "無効" };
+    Ok(format!(
+        "再生していない間のVLCソース非表示を{}にしました",
+        status
+    ))
+}
+
+#[tauri::command]
+async fn stop_playback(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let obs = connect_stored_obs(&state).await?;
+    // 停止すると再生中のwatcherが後始末をする
+    obs.stop_vlc_source(obs::UNIQUE_REPLAY_SOURCE_NAME).await?;
+    // 比較用のソースはまだ作られていないことがある
+    for source in VlcManager::DUAL_SOURCES {
+        if let Err(e) = obs.stop_vlc_source(source).await {
+            debug!("{}", e);
+        }
+    }
+    Ok("ハイライト再生を停止しました".to_string())
+}
+
+#[tauri::command]
+async fn set_require_active_output(
+    enabled: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    *state.require_active_output.write().unwrap() = enabled;
+    let status = if enabled { "有効" } else { "無効" };
+    Ok(format!("配信・録画中のみ保存を{}にしました", status))
+}
+
+#[derive(Serialize, Clone)]
+struct CaptureSkippedPayload {
+    cmd: MugiCmd,
+}
+
+impl AppEvent for CaptureSkippedPayload {
+    const NAME: &'static str = "capture-skipped-inactive";
+}
+
+// 送信側の再送で同じトリガーが続けて届いたときに1つにまとめる時間
+const DUPLICATE_TRIGGER_WINDOW: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[derive(Serialize, Clone)]
+struct DuplicateTriggerPayload {
+    cmd: MugiCmd,
+    event_id: Option<String>,
+}
+
+impl AppEvent for DuplicateTriggerPayload {
+    const NAME: &'static str = "duplicate-trigger-suppressed";
+}
+
+#[derive(Serialize, Clone)]
+struct TriggerReceivedPayload {
+    cmd: MugiCmd,
+    source: Option<IpAddr>,
+    // unix time(ms)
+    timestamp: u64,
+    // 保存に進んだか（トリガーでないコマンドや、重複・一時停止などで飛ばしたものはfalse）
+    fired: bool,
+}
+
+impl AppEvent for TriggerReceivedPayload {
+    const NAME: &'static str = "trigger-received";
+}
+
+// パースできたコマンドはトリガーでなくてもすべてフロントエンドに流す
+fn emit_trigger_received(
+    app_handle: &AppHandle,
+    cmd: MugiCmd,
+    source: Option<IpAddr>,
+    timestamp: u64,
+    fired: bool,
+) {
+    let payload = TriggerReceivedPayload {
+        cmd,
+        source,
+        timestamp,
+        fired,
+    };
+    EventSender::new(app_handle.clone()).emit(payload);
+}
+
+// require_active_outputが無効なら常にtrue
+// 未接続や確認に失敗した場合はクリップを失わないよう保存する側に倒す
+async fn is_output_active_for_capture(state: &AppState) -> bool {
+    if !*state.require_active_output.read().unwrap() {
+        return true;
+    }
+    let Some(obs) = current_obs(state) else {
+        return true;
+    };
+    match obs.is_output_active().await {
+        Ok(active) => active,
+        Err(e) => {
+            warn!("{}", e);
+            true
+        }
+    }
+}
+
+// Noneで変換しない
+#[tauri::command]
+async fn set_transcode_settings(
+    settings: Option<TranscodeSettings>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let Some(mut settings) = settings else {
+        *state.transcode.write().unwrap() = None;
+        return Ok("保存後の変換を無効にしました".to_string());
+    };
+    settings.video_codec = settings.video_codec.trim().to_string();
+    if settings.video_codec.is_empty() {
+        return Err("コーデックを指定してください".to_string());
+    }
+    settings.container = settings
+        .container
+        .trim()
+        .trim_start_matches('.')
+        .to_lowercase();
+    if !SUPPORTED_EXTENSIONS.contains(&settings.container.as_str()) {
+        return Err(format!("対応していない形式です: {}", settings.container));
+    }
+    settings.video_bitrate_kbps = settings.video_bitrate_kbps.clamp(500, 100_000); // 500-100000kbpsの範囲制限
+    let message = format!(
+        "保存後に{}・{}kbpsの{}に変換します",
+        settings.video_codec, settings.video_bitrate_kbps, settings.container
+    );
+    *state.transcode.write().unwrap() = Some(settings);
+    Ok(message)
+}
+
+// 0で確認しない
+#[tauri::command]
+async fn set_min_free_space(mb: u64, state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let mut min_free_space = state.min_free_space_mb.write().unwrap();
+    *min_free_space = mb;
+    Ok(format!("最低空き容量を{}MBに設定しました", mb))
+}
+
+#[tauri::command]
+async fn set_disk_full_policy(
+    policy: DiskFullPolicy,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mut disk_full_policy = state.disk_full_policy.write().unwrap();
+    *disk_full_policy = policy;
+    Ok(format!("容量不足時の動作を{:?}に設定しました", policy))
+}
+
+// 空文字で解除。次に接続したときから反映される
+#[tauri::command]
+async fn set_obs_profile(
+    name: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let name = name.trim();
+    let new_profile = (!name.is_empty()).then(|| name.to_string());
+    let message = match &new_profile {
+        Some(name) => format!("接続時にプロファイルを{}に切り替えます", name),
+        None => "接続時のプロファイル切り替えを解除しました".to_string(),
+    };
+    *state.obs_profile.write().unwrap() = new_profile;
+    Ok(message)
+}
+
+// 空文字で解除。次に接続したときから反映される
+#[tauri::command]
+async fn set_obs_scene_collection(
+    name: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let name = name.trim();
+    let new_collection = (!name.is_empty()).then(|| name.to_string());
+    let message = match &new_collection {
+        Some(name) => format!("接続時にシーンコレクションを{}に切り替えます", name),
+        None => "接続時のシーンコレクション切り替えを解除しました".to_string(),
+    };
+    *state.obs_scene_collection.write().unwrap() = new_collection;
+    Ok(message)
+}
+
+#[tauri::command]
+async fn set_instant_replay(
+    enabled: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    {
+        let mut instant_replay = state.instant_replay.write().unwrap();
+        *instant_replay = enabled;
+    }
+
+    let status = if enabled { "有効" } else { "無効" };
+    Ok(format!("保存直後の自動再生を{}にしました", status))
+}
+
+#[tauri::command]
+async fn set_transition_aware(
+    enabled: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    {
+        let mut transition_aware = state.transition_aware.write().unwrap();
+        *transition_aware = enabled;
+    }
+
+    let status = if enabled { "有効" } else { "無効" };
+    Ok(format!("トランジション中の保存待ちを{}にしました", status))
+}
+
+// 次にシステムを開始したときから反映される
+#[tauri::command]
+async fn set_channel_capacity(
+    capacity: usize,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let clamped_capacity = capacity.clamp(8, 1024); // 8-1024件の範囲制限
+    *state.channel_capacity.write().unwrap() = clamped_capacity;
+    Ok(format!(
+        "チャンネルの容量を{}件に設定しました",
+        clamped_capacity
+    ))
+}
+
+#[derive(Serialize)]
+struct ChannelDrops {
+    udp: u64,
+    replay: u64,
+}
+
+// 起動してからチャンネルが満杯で捨てたメッセージの数
+#[tauri::command]
+async fn get_channel_drops(state: tauri::State<'_, AppState>) -> Result<ChannelDrops, String> {
+    Ok(ChannelDrops {
+        udp: *state.udp_channel_drops.lock().unwrap(),
+        replay: *state.replay_channel_drops.lock().unwrap(),
+    })
+}
+
+// 動作中の待ち受けには次に開始したときから反映される
+#[tauri::command]
+async fn set_udp_framing(
+    framing: UdpFraming,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mut udp_framing = state.udp_framing.write().unwrap();
+    *udp_framing = framing;
+    Ok(format!("UDPコマンドの区切りを{:?}に設定しました", framing))
+}
+
+// 0で待たない。起動直後の接続（自動接続含む）にだけ効く
+#[tauri::command]
+async fn set_startup_delay(
+    delay_sec: u64,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let clamped_delay = delay_sec.min(MAX_STARTUP_DELAY_SEC); // 0-120秒の範囲制限
+    *state.startup_delay_sec.write().unwrap() = clamped_delay;
+    // 次回の起動時に使うので保存する
+    let mut prefs = preferences::load()?;
+    prefs.startup_delay_sec = Some(clamped_delay);
+    preferences::save(&prefs)?;
+    Ok(format!(
+        "起動時の接続待ちを{}秒に設定しました",
+        clamped_delay
+    ))
+}
+
+// Noneで無制限
+#[tauri::command]
+async fn set_max_clips(
+    max: Option<usize>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    if max == Some(0) {
+        return Err("最大クリップ数は1以上を指定してください".to_string());
+    }
+    {
+        let mut max_clips = state.max_clips.write().unwrap();
+        *max_clips = max;
+    }
+    Ok(match max {
+        Some(max) => format!("最大クリップ数を{}に設定しました", max),
+        None => "最大クリップ数を無制限にしました".to_string(),
+    })
+}
+
+#[tauri::command]
+async fn set_highlight_fit(
+    fit: HighlightFit,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mut highlight_fit = state.highlight_fit.write().unwrap();
+    *highlight_fit = fit;
+    Ok(format!("VLCソースの配置を{:?}に設定しました", fit))
+}
+
+// Noneでhighlight_fitによる配置に戻す
+#[tauri::command]
+async fn set_playback_transform(
+    transform: Option<SourceTransform>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    if let Some(transform) = transform {
+        // 接続中ならキャンバスサイズで確認する（再生時にも確認する）
+        let (canvas_width, canvas_height) = match current_obs(&state) {
+            Some(obs) => obs.get_canvas_size().await?,
+            None => (f32::MAX, f32::MAX),
+        };
+        transform.validate(canvas_width, canvas_height)?;
+    }
+    *state.playback_transform.write().unwrap() = transform;
+    Ok(match transform {
+        Some(t) => format!(
+            "VLCソースを({}, {})に{}x{}で再生します",
+            t.x, t.y, t.width, t.height
+        ),
+        None => "VLCソースの配置をhighlight_fitに戻しました".to_string(),
+    })
+}
+
+// transition_typeが空文字で解除
+#[tauri::command]
+async fn set_scene_transition(
+    transition_type: String,
+    duration_ms: u64,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mut scene_transition = state.scene_transition.write().unwrap();
+    if transition_type.is_empty() {
+        *scene_transition = None;
+        return Ok("トランジションの指定を解除しました".to_string());
+    }
+    let message = format!(
+        "トランジションを{}({}ms)に設定しました",
+        transition_type, duration_ms
+    );
+    *scene_transition = Some(SceneTransition {
+        name: transition_type,
+        duration_ms,
+    });
+    Ok(message)
+}
+
+// 空文字で解除
+#[tauri::command]
+async fn set_highlights_scene(
+    scene: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let scene = scene.trim();
+    let new_scene = (!scene.is_empty()).then(|| scene.to_string());
+    let message = match &new_scene {
+        Some(scene) => format!("ハイライト用シーンを{}に設定しました", scene),
+        None => "ハイライト用シーンを解除しました".to_string(),
+    };
+    {
+        let mut highlights_scene = state.highlights_scene.write().unwrap();
+        *highlights_scene = new_scene;
+    }
+    Ok(message)
+}
+
+#[tauri::command]
+async fn play_highlights(
+    video_paths: Vec<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<PlayHighlightsResult, String> {
+    // 再生リストが選択されていれば、渡されたクリップの代わりにそれを再生する
+    let active_clips = state
+        .playlists
+        .lock()
+        .unwrap()
+        .active_clips()
+        .map(<[PathBuf]>::to_vec);
+    // ファイル名からPathBufに変換（仮想的なパスとして扱う）
+    let movie_pathes: Vec<std::path::PathBuf> = match active_clips {
+        Some(clips) => clips,
+        None => video_paths.iter().map(std::path::PathBuf::from).collect(),
+    };
+    play_highlight_clips(&state, app_handle, movie_pathes).await
+}
+
+async fn play_highlight_clips(
+    state: &AppState,
+    app_handle: AppHandle,
+    movie_pathes: Vec<PathBuf>,
+) -> Result<PlayHighlightsResult, String> {
+    if movie_pathes.is_empty() {
+        return Ok(PlayHighlightsResult {
+            message: "再生する動画がありません".to_string(),
+            warnings: Vec::new(),
+        });
+    }
+
+    // 再生できないかもしれない形式は止めずに警告だけ返す
+    let warnings: Vec<FormatWarning> = movie_pathes
+        .iter()
+        .filter_map(|path| vlc_manager::check_playback_format(path))
+        .collect();
+    for warning in &warnings {
+        warn!("{:?}: {}", warning.path, warning.reason);
+    }
+
+    play_clips(state, app_handle, &movie_pathes).await?;
+
+    Ok(PlayHighlightsResult {
+        message: format!("{}個のハイライト動画を再生しました", movie_pathes.len()),
+        warnings,
+    })
+}
+
+// 同名の再生リストは上書き
+#[tauri::command]
+async fn create_playlist(
+    name: String,
+    paths: Vec<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("再生リスト名が空です".to_string());
+    }
+    let clips: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+    let count = clips.len();
+    state
+        .playlists
+        .lock()
+        .unwrap()
+        .insert(name.clone(), clips)?;
+    Ok(format!("再生リスト{}を保存しました（{}個）", name, count))
+}
+
+// 空文字で解除（play_highlightsは渡されたクリップを再生する）
+#[tauri::command]
+async fn select_playlist(
+    name: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let name = name.trim();
+    let mut playlists = state.playlists.lock().unwrap();
+    if name.is_empty() {
+        playlists.select(None)?;
+        return Ok("再生リストの選択を解除しました".to_string());
+    }
+    playlists.select(Some(name.to_string()))?;
+    Ok(format!("再生リストを{}に切り替えました", name))
+}
+
+#[tauri::command]
+async fn play_playlist(
+    name: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<PlayHighlightsResult, String> {
+    let clips = state.playlists.lock().unwrap().get(&name)?.to_vec();
+    play_highlight_clips(&state, app_handle, clips).await
+}
+
+#[tauri::command]
+async fn list_playlists(state: tauri::State<'_, AppState>) -> Result<NamedPlaylists, String> {
+    Ok(state.playlists.lock().unwrap().clone())
+}
+
+#[derive(Serialize, Clone)]
+struct PlayHighlightsResult {
+    message: String,
+    warnings: Vec<FormatWarning>,
+}
+
+// 先頭を読めるか確認する
+fn is_clip_readable(path: &Path) -> bool {
+    use std::io::Read;
+    let mut buf = [0u8; 1];
+    match std::fs::File::open(path) {
+        Ok(mut file) => file.read(&mut buf).is_ok_and(|n| n > 0),
+        Err(_) => false,
+    }
+}
+
+// VLCソースのキャッシュ時間。次回の再生から反映される
+#[tauri::command]
+async fn set_playback_buffering(
+    ms: u64,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let clamped_ms = ms.clamp(100, 60000); // OBSのVLCソースと同じ100-60000msの範囲制限
+    *state.playback_buffering_ms.write().unwrap() = clamped_ms;
+    Ok(format!(
+        "再生のキャッシュ時間を{}msに設定しました",
+        clamped_ms
+    ))
+}
+
+// 動作確認用に1本だけ再生する
+#[tauri::command]
+async fn play_single_clip(
+    path: String,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    let path = PathBuf::from(path);
+    vlc_manager::validate_clip_path(&path)?;
+    play_clips(&state, app_handle, std::slice::from_ref(&path)).await?;
+    Ok(format!("{}を再生しました", path.display()))
+}
+
+// シーンコレクションの読み込みが遅いと失敗することがあるので数回試す
+const VLC_INIT_ATTEMPTS: u32 = 3;
+const VLC_INIT_RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+async fn init_vlc_source_or_degrade(obs: &obs::Obs, state: &AppState) {
+    let mut result = Ok(());
+    for attempt in 1..=VLC_INIT_ATTEMPTS {
+        let caching_ms = *state.playback_buffering_ms.read().unwrap();
+        result = obs
+            .init_vlc_source(obs::UNIQUE_REPLAY_SOURCE_NAME, caching_ms)
+            .await;
+        match &result {
+            Ok(()) => break,
+            Err(e) => warn!(
+                "Failed to init VLC source (attempt {}/{}): {}",
+                attempt, VLC_INIT_ATTEMPTS, e
+            ),
+        }
+        if attempt < VLC_INIT_ATTEMPTS {
+            tokio::time::sleep(VLC_INIT_RETRY_INTERVAL).await;
+        }
+    }
+    if result.is_err() {
+        error!("VLC source is unavailable, highlights playback is disabled");
+    }
+    let mut is_playback_unavailable = state.is_playback_unavailable.lock().unwrap();
+    *is_playback_unavailable = result.is_err();
+}
+
+async fn play_clips(
+    state: &AppState,
+    app_handle: AppHandle,
+    movie_pathes: &[PathBuf],
+) -> Result<(), String> {
+    play_clip_lists(state, app_handle, &[movie_pathes]).await
+}
+
+// 2つの再生リストを比較用のVLCソースで左右に並べて再生する
+#[tauri::command]
+async fn play_highlights_dual(
+    left: Vec<String>,
+    right: Vec<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    if left.is_empty() || right.is_empty() {
+        return Err("左右それぞれに再生する動画を指定してください".to_string());
+    }
+    let left: Vec<PathBuf> = left.iter().map(PathBuf::from).collect();
+    let right: Vec<PathBuf> = right.iter().map(PathBuf::from).collect();
+    play_clip_lists(&state, app_handle, &[&left, &right]).await?;
+    Ok(format!(
+        "{}個と{}個のハイライト動画を並べて再生しました",
+        left.len(),
+        right.len()
+    ))
+}
+
+// OBSを通さずにクリップを再生する別ウィンドウ
+const HIGHLIGHT_WINDOW_LABEL: &str = "highlights";
+
+#[derive(Serialize, Clone)]
+struct HighlightWindowClipPayload {
+    // Noneなら再生するクリップが残っていない
+    path: Option<PathBuf>,
+}
+
+// 再生用ウィンドウを開いてクリップを順に再生する（再生はフロントエンドで行う）
+#[tauri::command]
+async fn play_highlights_window(
+    paths: Vec<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    if paths.is_empty() {
+        return Err("再生する動画がありません".to_string());
+    }
+    let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+    for path in &paths {
+        vlc_manager::validate_clip_path(path)?;
+    }
+    let count = paths.len();
+    *state.window_queue.lock().unwrap() = paths.into();
+
+    // 開いているウィンドウはすぐに新しいリストに切り替える
+    // 新しく開いたウィンドウは準備ができたらwindow_nextを呼ぶ
+    if app_handle
+        .get_webview_window(HIGHLIGHT_WINDOW_LABEL)
+        .is_some()
+    {
+        window_next(state, app_handle).await?;
+    } else {
+        tauri::WebviewWindowBuilder::new(
+            &app_handle,
+            HIGHLIGHT_WINDOW_LABEL,
+            tauri::WebviewUrl::App("index.html".into()),
+        )
+        .title("RLHighlight - Highlights")
+        .inner_size(1280.0, 720.0)
+        .build()
+        .map_err(|e| format!("Failed to open highlight window: {e}"))?;
+    }
+    Ok(format!(
+        "{}個のハイライト動画をウィンドウで再生します",
+        count
+    ))
+}
+
+// 次のクリップを再生用ウィンドウに送る。残っていなければウィンドウを閉じる
+#[tauri::command]
+async fn window_next(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<Option<PathBuf>, String> {
+    let path = state.window_queue.lock().unwrap().pop_front();
+    *state.window_clip.lock().unwrap() = path.clone();
+    let Some(window) = app_handle.get_webview_window(HIGHLIGHT_WINDOW_LABEL) else {
+        return Err("再生用ウィンドウが開いていません".to_string());
+    };
+    let payload = HighlightWindowClipPayload { path: path.clone() };
+    if let Err(e) = app_handle.emit_to(HIGHLIGHT_WINDOW_LABEL, "highlight-window-clip", payload) {
+        error!("Failed to emit highlight-window-clip event: {}", e);
+    }
+    if path.is_none() {
+        window
+            .close()
+            .map_err(|e| format!("Failed to close highlight window: {e}"))?;
+    }
+    Ok(path)
+}
+
+#[tauri::command]
+async fn window_stop(
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    clear_window_playback(&state);
+    let Some(window) = app_handle.get_webview_window(HIGHLIGHT_WINDOW_LABEL) else {
+        return Ok("再生用ウィンドウは開いていません".to_string());
+    };
+    window
+        .close()
+        .map_err(|e| format!("Failed to close highlight window: {e}"))?;
+    Ok("ウィンドウでの再生を停止しました".to_string())
+}
+
+// 閉じたウィンドウのクリップを読めなくする
+fn clear_window_playback(state: &AppState) {
+    state.window_queue.lock().unwrap().clear();
+    *state.window_clip.lock().unwrap() = None;
+}
+
+// 再生用ウィンドウのvideoが読むプロトコル
+// assetプロトコルの許可は取り消せないので、ウィンドウに送ったクリップだけをここで返す
+const WINDOW_CLIP_SCHEME: &str = "clip";
+// 1回の応答で読む上限（videoは続きをRangeで要求する）
+const WINDOW_CLIP_CHUNK_BYTES: u64 = 4 * 1024 * 1024;
+
+async fn serve_window_clip(
+    state: &AppState,
+    request: &tauri::http::Request<Vec<u8>>,
+) -> tauri::http::Response<Vec<u8>> {
+    match read_window_clip(state, request).await {
+        Ok(response) => response,
+        Err(status) => tauri::http::Response::builder()
+            .status(status)
+            .body(Vec::new())
+            .unwrap(),
+    }
+}
+
+async fn read_window_clip(
+    state: &AppState,
+    request: &tauri::http::Request<Vec<u8>>,
+) -> Result<tauri::http::Response<Vec<u8>>, tauri::http::StatusCode> {
+    use tauri::http::{StatusCode, header};
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    // convertFileSrcはパス全体を1つのセグメントとしてエンコードする
+    let path = percent_decode(request.uri().path().trim_start_matches('/'))
+        .map(PathBuf::from)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    if state.window_clip.lock().unwrap().as_ref() != Some(&path) {
+        warn!("Refused to serve {:?} to the highlight window", path);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let mut file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let len = file
+        .metadata()
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .len();
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("bytes=0-");
+    let (start, end) = parse_byte_range(range, len).ok_or(StatusCode::RANGE_NOT_SATISFIABLE)?;
+    let end = end.min(start + WINDOW_CLIP_CHUNK_BYTES - 1);
+    let mut body = vec![0; (end - start + 1) as usize];
+    file.seek(std::io::SeekFrom::Start(start))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    file.read_exact(&mut body)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    tauri::http::Response::builder()
+        .status(StatusCode::PARTIAL_CONTENT)
+        .header(header::CONTENT_TYPE, clip_mime_type(&path))
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_RANGE, format!("bytes {start}-{end}/{len}"))
+        .header(header::CONTENT_LENGTH, body.len())
+        .body(body)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+// %XXを戻す。不正なエスケープやUTF-8でなければNone
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+// "bytes=start-end"（endは省略可）の範囲をファイルの長さに収める
+// 末尾からの指定(bytes=-500)はvideoが使わないので扱わない
+fn parse_byte_range(range: &str, len: u64) -> Option<(u64, u64)> {
+    let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+    let start: u64 = start.trim().parse().ok()?;
+    let last = len.checked_sub(1)?;
+    let end = match end.trim() {
+        "" => last,
+        end => end.parse::<u64>().ok()?.min(last),
+    };
+    (start <= end).then_some((start, end))
+}
+
+fn clip_mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "mkv" => "video/x-matroska",
+        "ts" => "video/mp2t",
+        "flv" => "video/x-flv",
+        _ => "application/octet-stream",
+    }
+}
+
+fn playback_options(state: &AppState) -> PlaybackOptions {
+    PlaybackOptions {
+        highlights_scene: state.highlights_scene.read().unwrap().clone(),
+        fit: *state.highlight_fit.read().unwrap(),
+        transform: *state.playback_transform.read().unwrap(),
+        loop_playlist: *state.playback_loop.read().unwrap(),
+        transition: state.scene_transition.read().unwrap().clone(),
+        caching_ms: *state.playback_buffering_ms.read().unwrap(),
+    }
+}
+
+// 再生中のハイライトの後ろにクリップを足して、プレイリストの長さを返す
+// 再生し終えていれば足したクリップから再生し直す
+#[tauri::command]
+async fn append_to_playback(
+    paths: Vec<String>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<usize, String> {
+    if paths.is_empty() {
+        return Err("追加する動画がありません".to_string());
+    }
+    let paths: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+    // モックのクリップは実在しないので確認しない
+    if state.mock_obs.read().unwrap().is_none() {
+        for path in &paths {
+            vlc_manager::validate_clip_path(path)?;
+        }
+    }
+    let obs = connect_stored_obs(&state).await?;
+    let active = obs
+        .is_vlc_source_active(obs::UNIQUE_REPLAY_SOURCE_NAME)
+        .await?;
+    if !active {
+        play_clips(&state, app_handle, &paths).await?;
+        return Ok(paths.len());
+    }
+    // 足した分だけ再生の期限を延ばす
+    let added = total_clip_duration(&paths).await;
+    {
+        let mut deadline = state.playback_deadline.lock().unwrap();
+        *deadline = deadline
+            .zip(added)
+            .map(|(deadline, added)| deadline + added);
+    }
+    let playlist = {
+        let mut playlist = state.playback_playlist.lock().unwrap();
+        playlist.extend(paths);
+        playlist.clone()
+    };
+    obs.update_vlc_playlist(
+        obs::UNIQUE_REPLAY_SOURCE_NAME,
+        &playlist,
+        &playback_options(&state),
+    )
+    .await?;
+    Ok(playlist.len())
+}
+
+// 再生し終えるまでの時間の目安に足す余裕（読み込みやシーンの切り替え）
+const PLAYBACK_DEADLINE_MARGIN: std::time::Duration = std::time::Duration::from_secs(30);
+
+// クリップの長さの合計。1つでも分からなければNone
+async fn total_clip_duration(clips: &[PathBuf]) -> Option<std::time::Duration> {
+    let durations =
+        futures_util::future::join_all(clips.iter().map(|clip| ffmpeg::probe_duration(clip))).await;
+    durations
+        .into_iter()
+        .map(|duration| duration.ok())
+        .sum::<Option<f64>>()
+        .and_then(|sec| std::time::Duration::try_from_secs_f64(sec).ok())
+}
+
+#[derive(Serialize, Clone)]
+struct VlcSourceRecreatedPayload;
+
+impl AppEvent for VlcSourceRecreatedPayload {
+    const NAME: &'static str = "vlc-source-recreated";
+}
+
+// VLCソースで再生し、終了後の後始末をwatcherに任せる
+// リストが1つなら通常のVLCソース、2つなら比較用のVLCソースを左右に並べる
+async fn play_clip_lists(
+    state: &AppState,
+    app_handle: AppHandle,
+    clip_lists: &[&[PathBuf]],
+) -> Result<(), String> {
+    if *state.is_playback_unavailable.lock().unwrap() {
+        return Err(
+            "VLCソースの初期化に失敗したため再生できません。OBSに再接続してください".to_string(),
+        );
+    }
+    // 再生の途中で止まらないように、読めないファイルがあれば始める前に断る
+    // モックのクリップは実在しないので確認しない
+    if state.mock_obs.read().unwrap().is_none() {
+        let unreadable: Vec<String> = clip_lists
+            .iter()
+            .flat_map(|clips| clips.iter())
+            .filter(|path| !is_clip_readable(path))
+            .map(|path| path.display().to_string())
+            .collect();
+        if !unreadable.is_empty() {
+            return Err(format!(
+                "読み込めない動画があります: {}",
+                unreadable.join(", ")
+            ));
+        }
+    }
+    let obs = connect_stored_obs(state).await?;
+
+    let options = playback_options(state);
+    let (sources, playlists): (Vec<&'static str>, Vec<VlcPlaylist>) = match clip_lists {
+        [left, right] => {
+            let (canvas_width, canvas_height) = obs.get_canvas_size().await?;
+            let half_width = canvas_width / 2.0;
+            let playlists = VlcManager::DUAL_SOURCES
+                .into_iter()
+                .zip([(*left, 0.0), (*right, half_width)])
+                .map(|(source, (clips, x))| VlcPlaylist {
+                    source,
+                    clips,
+                    transform: Some(SourceTransform {
+                        x,
+                        y: 0.0,
+                        width: half_width,
+                        height: canvas_height,
+                    }),
+                })
+                .collect();
+            (VlcManager::DUAL_SOURCES.to_vec(), playlists)
+        }
+        _ => {
+            let playlists = clip_lists
+                .iter()
+                .map(|&clips| VlcPlaylist {
+                    source: obs::UNIQUE_REPLAY_SOURCE_NAME,
+                    clips,
+                    transform: options.transform,
+                })
+                .collect();
+            (vec![obs::UNIQUE_REPLAY_SOURCE_NAME], playlists)
+        }
+    };
+    let is_dual = sources.len() > 1;
+
+    // 音声設定は通常のVLCソースのみ
+    let prev_audio = if is_dual {
+        None
+    } else {
+        let audio_mode = *state.playback_audio_mode.read().unwrap();
+        obs.apply_playback_audio_mode(audio_mode).await
+    };
+
+    let freeze_intro =
+        std::time::Duration::from_millis(*state.playback_freeze_intro_ms.read().unwrap());
+    // 再生が止まったまま終わらなくても後始末できるように、リストの長さから期限を決める
+    // ループ再生はstop_playbackまで続けるので期限なし
+    let expected = if options.loop_playlist {
+        vec![None; clip_lists.len()]
+    } else {
+        futures_util::future::join_all(clip_lists.iter().map(|clips| total_clip_duration(clips)))
+            .await
+    };
+    let now = tokio::time::Instant::now();
+    let to_deadline = |expected: Option<std::time::Duration>| {
+        expected.map(|expected| now + freeze_intro + expected + PLAYBACK_DEADLINE_MARGIN)
+    };
+    // 比較再生は左右で長さが違うので、ソースごとに期限を持つ
+    let deadlines: Vec<PlaybackDeadline> = if is_dual {
+        expected
+            .into_iter()
+            .map(|expected| Arc::new(Mutex::new(to_deadline(expected))))
+            .collect()
+    } else {
+        let longest = expected
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .and_then(|durations| durations.into_iter().max());
+        *state.playback_deadline.lock().unwrap() = to_deadline(longest);
+        vec![state.playback_deadline.clone()]
+    };
+
+    // VLCソースで動画再生
+    let started = match obs.play_vlc_sources(&playlists, &options).await {
+        Ok(started) => started,
+        Err(e) => {
+            if let Some(prev) = prev_audio {
+                obs.restore_audio_state(prev).await;
+            }
+            return Err(format!("Failed to play VLC source: {}", e));
+        }
+    };
+    if !is_dual {
+        *state.playback_playlist.lock().unwrap() = clip_lists.concat();
+    }
+    // 比較用のソースは初回の再生で作るので通知しない
+    if started.recreated_source && !is_dual {
+        EventSender::new(app_handle.clone()).emit(VlcSourceRecreatedPayload);
+    }
+
+    spawn_playback_watcher(
+        obs,
+        sources,
+        deadlines,
+        freeze_intro,
+        prev_audio,
+        started.original_scene,
+        options.transition,
+        *state.hide_when_idle.read().unwrap(),
+        app_handle,
+    );
+    Ok(())
+}
+
+#[tauri::command]
+async fn connect_obs(
+    host: String,
+    port: u16,
+    password: Option<String>,
+    secure: Option<bool>,
+    state: tauri::State<'_, AppState>,
+    app_handle: tauri::AppHandle,
+) -> Result<String, String> {
+    // wss://で接続するか（証明書は検証する）
+    let secure = secure.unwrap_or(false);
+    info!(
+        "Attempting to connect to OBS at {}:{} (secure: {})",
+        host, port, secure
+    );
+
+    // 接続中の呼び出しが終わるまで後から来たものは何もしない
+    // 先に取らないと、確認の直後に別の接続が終わってシステムが二重に動く
+    let Some(_connecting) = BusyGuard::acquire(&state.is_connecting) else {
+        return Err("既にOBSに接続中です".to_string());
+    };
+    // 既にシステムが動作中の場合はエラー
+    {
+        let is_running = state.is_system_running.lock().unwrap();
+        if *is_running {
+            return Err("システムは既に動作中です".to_string());
+        }
+    }
+
+    wait_startup_delay(&state).await;
+    let mut obs = new_obs(&state);
+    let password_ref = password.as_deref();
+
+    // OBS接続試行
+    match obs.connect(&host, port, password_ref, secure).await {
+        Ok(_) => {
+            info!("Connected to OBS successfully");
+
+            // VLCソースやリプレイの設定を確認する前に切り替える
+            switch_obs_context(&obs, &state).await?;
+
+            // 開始する前に長さを確認する（既に動いているバッファを伸ばすと再起動になる）
+            let buffer_warning = match enforce_min_buffer_length(&obs, &state).await {
+                Ok(warning) => warning,
+                Err(e) => {
+                    warn!("{}", e);
+                    None
+                }
+            };
+
+            // リプレイバッファ設定
+            if let Err(e) = obs.set_replay_buffer().await {
+                return Err(format!("Failed to set replay buffer: {}", e));
+            }
+
+            // VLCソース初期化（失敗しても保存だけはできるようにする）
+            init_vlc_source_or_degrade(&obs, &state).await;
+
+            // 接続情報を保存
+            {
+                let mut conn_info = state.obs_connection_info.lock().unwrap();
+                *conn_info = Some((host.clone(), port, password.clone(), secure));
+            }
+
+            remember_connection(&state);
+
+            // システム開始
+            start_system(host, port, password, secure, state, app_handle).await?;
+
+            Ok(match buffer_warning {
+                Some(warning) => format!("OBS接続に成功しました（{}）", warning),
+                None => "OBS接続に成功しました".to_string(),
+            })
+        }
+        Err(e) => {
+            error!("Failed to connect to OBS: {}", e);
+            Err(format!("OBS接続に失敗しました: {}", e))
+        }
+    }
+}
+
+// 接続・キャプチャなどの処理中の印。dropで外すので途中で失敗・中断しても残らない
+struct BusyGuard(Arc<Mutex<bool>>);
+
+impl BusyGuard {
+    // 既に処理中ならNone
+    fn acquire(flag: &Arc<Mutex<bool>>) -> Option<Self> {
+        let mut connecting = flag.lock().unwrap();
+        if *connecting {
+            return None;
+        }
+        *connecting = true;
+        Some(Self(flag.clone()))
+    }
+}
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        *self.0.lock().unwrap() = false;
+    }
+}
+
+// 設定されていればOBSのシーンコレクションとプロファイルを切り替える
+async fn switch_obs_context(obs: &obs::Obs, state: &AppState) -> Result<(), String> {
+    let scene_collection = state.obs_scene_collection.read().unwrap().clone();
+    if let Some(name) = scene_collection {
+        obs.set_current_scene_collection(&name)
+            .await
+            .map_err(|e| format!("シーンコレクションを切り替えられませんでした: {}", e))?;
+        info!("Switched OBS scene collection to {}", name);
+    }
+    let profile = state.obs_profile.read().unwrap().clone();
+    if let Some(name) = profile {
+        obs.set_current_profile(&name)
+            .await
+            .map_err(|e| format!("プロファイルを切り替えられませんでした: {}", e))?;
+        info!("Switched OBS profile to {}", name);
+    }
+    Ok(())
+}
+
+// 短すぎるリプレイバッファを伸ばすか、警告の文言を返す
+// 伸ばすのはバッファを開始する前にする。既に動いていれば再起動になり、溜まっていた映像が消えるので知らせる
+async fn enforce_min_buffer_length(
+    obs: &obs::Obs,
+    state: &AppState,
+) -> Result<Option<String>, String> {
+    let Some(min_sec) = *state.min_buffer_length_sec.read().unwrap() else {
+        return Ok(None);
+    };
+    let length_sec = obs.get_replay_buffer_length().await?;
+    if length_sec >= min_sec {
+        return Ok(None);
+    }
+    if *state.raise_buffer_length.read().unwrap() {
+        obs.set_replay_buffer_length(min_sec).await?;
+        info!(
+            "Raised replay buffer length from {}s to {}s",
+            length_sec, min_sec
+        );
+        if !obs.is_replay_buffer_active().await? {
+            return Ok(None);
+        }
+        obs.restart_replay_buffer().await?;
+        warn!("Restarted replay buffer to apply the new length, buffered footage was discarded");
+        return Ok(Some(format!(
+            "リプレイバッファを{}秒に伸ばすために再起動したので、それまでに溜まっていた映像は消えました",
+            min_sec
+        )));
+    }
+    warn!(
+        "Replay buffer length {}s is shorter than {}s",
+        length_sec, min_sec
+    );
+    Ok(Some(format!(
+        "OBSのリプレイバッファが{}秒しかありません。{}秒以上にしてください",
+        length_sec, min_sec
+    )))
+}
+
+// Noneで確認しない。raiseがtrueなら次の接続時に自動で伸ばす
+#[tauri::command]
+async fn set_min_buffer_length(
+    min_sec: Option<u64>,
+    raise: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let clamped_min = min_sec.map(|sec| sec.clamp(1, 600)); // 1-600秒の範囲制限
+    *state.min_buffer_length_sec.write().unwrap() = clamped_min;
+    *state.raise_buffer_length.write().unwrap() = raise;
+    Ok(match clamped_min {
+        Some(sec) if raise => format!("リプレイバッファを{}秒以上に伸ばします", sec),
+        Some(sec) => format!("リプレイバッファが{}秒未満なら警告します", sec),
+        None => "リプレイバッファの長さを確認しません".to_string(),
+    })
+}
+
+#[tauri::command]
+async fn get_preference(
+    key: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<Option<serde_json::Value>, String> {
+    Ok(state.ui_preferences.lock().unwrap().get(&key))
+}
+
+// valueにnullを渡すとキーを消す
+#[tauri::command]
+async fn set_preference(
+    key: String,
+    value: serde_json::Value,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    state
+        .ui_preferences
+        .lock()
+        .unwrap()
+        .set(key.clone(), value)?;
+    Ok(format!("{}を保存しました", key))
+}
+
+#[tauri::command]
+async fn get_obs_output_status(
+    state: tauri::State<'_, AppState>,
+) -> Result<obs::OutputStatus, String> {
+    let obs = connect_stored_obs(&state).await?;
+    obs.get_output_status().await
+}
+
+#[tauri::command]
+async fn start_virtual_cam(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let obs = connect_stored_obs(&state).await?;
+    obs.start_virtual_cam().await?;
+    Ok("仮想カメラを開始しました".to_string())
+}
+
+#[tauri::command]
+async fn stop_virtual_cam(state: tauri::State<'_, AppState>) -> Result<String, String> {
+    let obs = connect_stored_obs(&state).await?;
+    obs.stop_virtual_cam().await?;
+    Ok("仮想カメラを停止しました".to_string())
+}
+
+#[tauri::command]
+async fn get_obs_stats(state: tauri::State<'_, AppState>) -> Result<obs::ObsStats, String> {
+    let obs = connect_stored_obs(&state).await?;
+    obs.get_stats().await
+}
+
+const OBS_STATS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+// 接続中は定期的にOBSの統計を通知する
+async fn emit_obs_stats(state: AppState, app_handle: AppHandle) {
+    let mut interval = tokio::time::interval(OBS_STATS_INTERVAL);
+    loop {
+        interval.tick().await;
+        // 再接続中は飛ばす
+        let Some(obs) = current_obs(&state) else {
+            continue;
+        };
+        match obs.get_stats().await {
+            Ok(stats) => {
+                EventSender::new(app_handle.clone()).emit(stats);
+            }
+            Err(e) => debug!("{}", e),
+        }
+    }
+}
 
-    // OBS接続を作成
-    let mut obs = obs::Obs::new();
-    let password_ref = password.as_deref();
-    obs.connect(&host, port, password_ref)
-        .await
-        .map_err(|e| format!("Failed to connect to OBS: {}", e))?;
+// 接続先を保存する。パスワードはできればOSのキーリングに入れる
+fn save_connection(
+    host: String,
+    port: u16,
+    password: Option<&str>,
+    secure: bool,
+) -> Result<PasswordStorage, String> {
+    let mut prefs = preferences::load()?;
+    let password = credentials::store_password(&host, port, password);
+    prefs.connection = Some(StoredConnection {
+        host,
+        port,
+        password: password.clone(),
+        secure,
+    });
+    preferences::save(&prefs)?;
+    Ok(password)
+}
 
-    // ファイル名からPathBufに変換（仮想的なパスとして扱う）
-    let movie_pathes: Vec<std::path::PathBuf> =
-        video_paths.iter().map(std::path::PathBuf::from).collect();
+// 保存した接続先とパスワード（host, port, password, secure）
+fn load_connection() -> Result<Option<(String, u16, Option<String>, bool)>, String> {
+    let Some(conn) = preferences::load()?.connection else {
+        return Ok(None);
+    };
+    let password = credentials::load_password(&conn.host, conn.port, &conn.password)?;
+    Ok(Some((conn.host, conn.port, password, conn.secure)))
+}
 
-    // VLCソースで動画再生
-    if let Err(e) = obs.play_vlc_source(&movie_pathes).await {
-        return Err(format!("Failed to play VLC source: {}", e));
+// 自動接続が有効なら次回起動用に接続先を保存する
+fn remember_connection(state: &AppState) {
+    match preferences::load() {
+        Ok(prefs) if prefs.autoconnect => {}
+        Ok(_) => return,
+        Err(e) => {
+            warn!("{}", e);
+            return;
+        }
+    }
+    let conn_info = state.obs_connection_info.lock().unwrap().clone();
+    let Some((host, port, password, secure)) = conn_info else {
+        return;
+    };
+    if let Err(e) = save_connection(host, port, password.as_deref(), secure) {
+        error!("{}", e);
     }
+}
 
-    Ok(format!(
-        "{}個のハイライト動画を再生しました",
-        video_paths.len()
-    ))
+#[derive(Serialize)]
+struct StoredCredentials {
+    host: String,
+    port: u16,
+    password: Option<String>,
+    secure: bool,
 }
 
 #[tauri::command]
-async fn connect_obs(
+async fn save_credentials(
     host: String,
     port: u16,
     password: Option<String>,
-    state: tauri::State<'_, AppState>,
-    app_handle: tauri::AppHandle,
+    secure: Option<bool>,
 ) -> Result<String, String> {
-    info!("Attempting to connect to OBS at {}:{}", host, port);
-
-    // 既にシステムが動作中の場合はエラー
-    {
-        let is_running = state.is_system_running.lock().unwrap();
-        if *is_running {
-            return Err("システムは既に動作中です".to_string());
+    let storage = save_connection(host, port, password.as_deref(), secure.unwrap_or(false))?;
+    Ok(match storage {
+        PasswordStorage::Plaintext { .. } => {
+            "OSのキーリングが使えないため、パスワードを平文で保存しました".to_string()
         }
-    }
-
-    let mut obs = obs::Obs::new();
-    let password_ref = password.as_deref();
+        _ => "接続情報を保存しました".to_string(),
+    })
+}
 
-    // OBS接続試行
-    match obs.connect(&host, port, password_ref).await {
-        Ok(_) => {
-            info!("Connected to OBS successfully");
+#[tauri::command]
+async fn load_credentials() -> Result<Option<StoredCredentials>, String> {
+    Ok(
+        load_connection()?.map(|(host, port, password, secure)| StoredCredentials {
+            host,
+            port,
+            password,
+            secure,
+        }),
+    )
+}
 
-            // リプレイバッファ設定
-            if let Err(e) = obs.set_replay_buffer().await {
-                return Err(format!("Failed to set replay buffer: {}", e));
-            }
+// キーリングのパスワードも消す
+#[tauri::command]
+async fn delete_credentials() -> Result<String, String> {
+    let mut prefs = preferences::load()?;
+    let Some(conn) = prefs.connection.take() else {
+        return Ok("保存された接続情報はありません".to_string());
+    };
+    credentials::delete_password(&conn.host, conn.port);
+    preferences::save(&prefs)?;
+    Ok("保存した接続情報を削除しました".to_string())
+}
 
-            // VLCソース初期化
-            if let Err(e) = obs.init_vlc_source().await {
-                return Err(format!("Failed to init VLC source: {}", e));
-            }
+#[tauri::command]
+async fn set_autoconnect(
+    enabled: bool,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let mut prefs = preferences::load()?;
+    prefs.autoconnect = enabled;
+    preferences::save(&prefs)?;
+    // 接続済みならその接続先を保存する
+    remember_connection(&state);
+    let status = if enabled { "有効" } else { "無効" };
+    Ok(format!("起動時の自動接続を{}にしました", status))
+}
 
-            // 接続情報を保存
-            {
-                let mut conn_info = state.obs_connection_info.lock().unwrap();
-                *conn_info = Some((host.clone(), port, password.clone()));
-            }
+#[derive(Serialize, Clone)]
+struct AutoconnectResultPayload {
+    success: bool,
+    message: String,
+}
 
-            // システム開始
-            start_system(host, port, password, state, app_handle).await?;
+impl AppEvent for AutoconnectResultPayload {
+    const NAME: &'static str = "autoconnect-result";
+}
 
-            Ok("OBS接続に成功しました".to_string())
-        }
+// 起動時に保存した接続先へ接続する
+async fn autoconnect(app_handle: AppHandle) {
+    let prefs = match preferences::load() {
+        Ok(prefs) => prefs,
         Err(e) => {
-            error!("Failed to connect to OBS: {}", e);
-            Err(format!("OBS接続に失敗しました: {}", e))
+            warn!("{}", e);
+            return;
         }
+    };
+    if !prefs.autoconnect {
+        return;
     }
+    let result = match load_connection() {
+        Ok(Some((host, port, password, secure))) => {
+            info!("Autoconnecting to OBS at {}:{}", host, port);
+            let state = app_handle.state::<AppState>();
+            connect_obs(
+                host,
+                port,
+                password,
+                Some(secure),
+                state,
+                app_handle.clone(),
+            )
+            .await
+        }
+        Ok(None) => {
+            info!("Autoconnect is enabled but no connection is stored");
+            return;
+        }
+        Err(e) => Err(e),
+    };
+    let payload = match result {
+        Ok(message) => AutoconnectResultPayload {
+            success: true,
+            message,
+        },
+        Err(message) => {
+            error!("Autoconnect failed: {}", message);
+            AutoconnectResultPayload {
+                success: false,
+                message,
+            }
+        }
+    };
+    EventSender::new(app_handle.clone()).emit(payload);
 }
 
 async fn start_system(
     host: String,
     port: u16,
     password: Option<String>,
+    secure: bool,
     state: tauri::State<'_, AppState>,
     app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
@@ -170,20 +4490,27 @@ async fn start_system(
     // 別タスクでメインシステムを起動
     let host_clone = host.clone();
     let password_clone = password.clone();
-    let sleep_duration_clone = state.sleep_duration_sec.clone();
-    tokio::spawn(async move {
+    let state_clone = state.inner().clone();
+    let system_task = tokio::spawn(async move {
         if let Err(e) = run_main_system(
             host_clone,
             port,
             password_clone,
-            sleep_duration_clone,
+            secure,
+            state_clone.clone(),
             app_handle,
         )
         .await
         {
             error!("Main system error: {}", e);
         }
+        // 自分で止まった場合もUDP・イベントリスナーを残さない
+        enter_idle(&state_clone);
     });
+    {
+        let mut task = state.system_task.lock().unwrap();
+        *task = Some(system_task);
+    }
 
     info!("RL Replay system started successfully");
     Ok(())
@@ -193,13 +4520,14 @@ async fn run_main_system(
     host: String,
     port: u16,
     password: Option<String>,
-    sleep_duration: Arc<RwLock<u64>>,
+    secure: bool,
+    state: AppState,
     app_handle: tauri::AppHandle,
 ) -> Result<(), String> {
     // OBS接続を再作成
-    let mut obs = obs::Obs::new();
+    let mut obs = new_obs(&state);
     let password_ref = password.as_deref();
-    obs.connect(&host, port, password_ref)
+    obs.connect(&host, port, password_ref, secure)
         .await
         .map_err(|e| format!("Failed to reconnect to OBS: {}", e))?;
 
@@ -207,44 +4535,314 @@ async fn run_main_system(
         .await
         .map_err(|e| format!("Failed to set replay buffer: {}", e))?;
 
-    obs.init_vlc_source()
-        .await
-        .map_err(|e| format!("Failed to init VLC source: {}", e))?;
+    init_vlc_source_or_degrade(&obs, &state).await;
 
     // VlcManager初期化
-    let vlc_manager = VlcManager::new();
+    // 偽物のクリップは実在しないので確認しない
+    let verify_clips = state.mock_obs.read().unwrap().is_none();
+    let vlc_manager = VlcManager::new(
+        state.clips.clone(),
+        state.replay_redirect.clone(),
+        verify_clips,
+    );
 
     // イベントリスナー設定
-    let (rb_tx, rb_rx) = mpsc::channel(32);
-    obs.set_event_listener(rb_tx)
-        .await
-        .map_err(|e| format!("Failed to set event listener: {}", e))?;
+    let capacity = *state.channel_capacity.read().unwrap();
+    let (rb_tx, rb_rx) = mpsc::channel(capacity);
+    vlc_manager.set_event_listener(rb_rx, EventSender::new(app_handle.clone()));
+    link_obs(&state, &app_handle, obs, rb_tx).await?;
+    let continuous = *state.capture_mode.read().unwrap() == CaptureMode::Continuous;
+    if continuous && let Some(obs) = current_obs(&state) {
+        obs.set_recording().await?;
+    }
 
-    vlc_manager.set_event_listener(rb_rx, app_handle.clone());
+    {
+        let mut session = state.session.lock().unwrap();
+        *session = Some(SessionManifest::new(host.clone(), unix_millis()));
+    }
+    reset_session_counter(&state, Some(SessionCounter::new()));
 
-    // UDPサーバー開始
-    let (tx, mut rx) = mpsc::channel::<String>(32);
-    tokio::spawn(async {
-        if let Err(e) = bind_socket(tx).await {
-            error!("UDP socket error: {}", e);
+    // UDPサーバー開始（落ちたら再bindする）
+    let (tx, mut rx) = mpsc::channel::<UdpMessage>(capacity);
+    let (udp_tx, udp_rx) = mpsc::channel::<UdpMessage>(capacity);
+    {
+        let mut udp_listener_tx = state.udp_listener_tx.lock().unwrap();
+        *udp_listener_tx = Some(udp_tx);
+    }
+    let udp_bind_addrs = state.udp_bind_addrs.read().unwrap().clone();
+    for addr in udp_bind_addrs {
+        if let Err(e) = spawn_udp_listener(&state, addr, &app_handle) {
+            warn!("{}", e);
         }
-    });
+    }
+    // 録画はUDPから来たものだけを対象にする
+    let _stats_task = AbortOnDrop(tokio::spawn(emit_obs_stats(
+        state.clone(),
+        app_handle.clone(),
+    )));
+    let _forward_task = AbortOnDrop(tokio::spawn(recording::forward(
+        udp_rx,
+        tx.clone(),
+        state.trigger_recording.clone(),
+    )));
+    {
+        let mut udp_msg_tx = state.udp_msg_tx.lock().unwrap();
+        *udp_msg_tx = Some(tx);
+    }
 
     // UDPメッセージ処理 - 無限ループで動作し続ける
-    while let Some(d) = rx.recv().await {
-        let cmd = mugi_schema::parse_cmd(&d);
+    // UntilNextEventで待っている間に受信したメッセージはnext_msgに入れて次に処理する
+    let mut next_msg: Option<UdpMessage> = None;
+    // 直前に保存したEpicSave（コンボ用）
+    let mut combo_candidate: Option<ComboCandidate> = None;
+    // 最後に受信した試合時間（タグ用）
+    let mut game_clock: Option<String> = None;
+    let mut ignored_count: u64 = 0;
+    // 最後にハートビートを受信した時刻（途切れたらNoneに戻す）
+    let mut last_heartbeat: Option<tokio::time::Instant> = None;
+    // 直近のトリガー（コマンドとイベントID、IDがなければコマンドのみ）と受信時刻
+    let mut recent_triggers: HashMap<(MugiCmd, Option<String>), std::time::Instant> =
+        HashMap::new();
+    let mut sequence_matcher = SequenceMatcher::default();
+    loop {
+        let incoming = match next_msg.take() {
+            Some(d) => Incoming::Udp(d),
+            None => match recv_or_heartbeat_lost(&mut rx, &state, &mut last_heartbeat).await {
+                Some(incoming) => incoming,
+                None => break,
+            },
+        };
+        // 内部で発行するコマンドにはデータがない
+        let (
+            UdpMessage {
+                data: d,
+                source,
+                port,
+            },
+            internal_cmd,
+        ) = match incoming {
+            Incoming::Udp(msg) => (msg, None),
+            Incoming::HeartbeatLost => (
+                UdpMessage {
+                    data: String::new(),
+                    source: None,
+                    port: None,
+                },
+                Some(MugiCmd::MatchEnded),
+            ),
+        };
+        if !*state.is_udp_processing.lock().unwrap() {
+            ignored_count += 1;
+            debug!("UDP processing disabled, ignored: {}", d);
+            let payload = UdpIgnoredPayload {
+                ignored_count,
+                source,
+            };
+            EventSender::new(app_handle.clone()).emit(payload);
+            continue;
+        }
+        let received_at = std::time::Instant::now();
+        let timestamp = unix_millis();
+        let cmd = match internal_cmd {
+            Some(cmd) => Ok(cmd),
+            None => mugi_schema::parse_cmd(&d),
+        };
         match cmd {
             Err(_) => error!("Failed to parse:{}", d),
             Ok(cmd) => {
-                if cmd == MugiCmd::Scored || cmd == MugiCmd::EpicSave {
+                // 試合終了でセッションを区切る
+                // 正常に終わった試合ではハートビートの途切れを試合終了扱いにしない
+                if cmd == MugiCmd::End {
+                    finish_session(&state, Some(SessionManifest::new(host.clone(), timestamp)));
+                    last_heartbeat = None;
+                }
+                if cmd == MugiCmd::Heartbeat {
+                    last_heartbeat = Some(tokio::time::Instant::now());
+                }
+                if cmd == MugiCmd::Time {
+                    game_clock = mugi_schema::parse_tags(&d).remove("time");
+                }
+                // 試合開始で集計をリセット
+                if cmd == MugiCmd::Start {
+                    reset_session_counter(&state, Some(SessionCounter::new()));
+                }
+                // time/boostのような定期送信は並びに含めない
+                let sequence = state.trigger_sequence.read().unwrap().clone();
+                let sequence_matched = !cmd.is_periodic()
+                    && sequence_matcher.feed(
+                        sequence.as_ref(),
+                        cmd,
+                        &mugi_schema::parse_tags(&d),
+                        received_at,
+                    );
+                let is_trigger = cmd == MugiCmd::MatchEnded
+                    || sequence_matched
+                    || state.trigger_cmds.read().unwrap().contains(&cmd);
+                if !is_trigger {
+                    emit_trigger_received(&app_handle, cmd, source, timestamp, false);
+                }
+                if is_trigger {
+                    recent_triggers
+                        .retain(|_, at| received_at.duration_since(*at) < DUPLICATE_TRIGGER_WINDOW);
+                    let event_id = mugi_schema::parse_event_id(&d);
+                    let key = (cmd, event_id);
+                    if recent_triggers.contains_key(&key) {
+                        debug!("Duplicate {:?} suppressed", key);
+                        let (cmd, event_id) = key;
+                        let payload = DuplicateTriggerPayload { cmd, event_id };
+                        EventSender::new(app_handle.clone()).emit(payload);
+                        emit_trigger_received(&app_handle, cmd, source, timestamp, false);
+                        continue;
+                    }
+                    recent_triggers.insert(key, received_at);
+                    if *state.is_capture_paused.lock().unwrap() {
+                        debug!("Capture paused, skip {:?}", cmd);
+                        emit_trigger_received(&app_handle, cmd, source, timestamp, false);
+                        continue;
+                    }
+                    let action = source
+                        .and_then(|ip| state.source_actions.read().unwrap().get(&ip).cloned());
+                    match action {
+                        Some(SourceAction::Ignore) => {
+                            debug!("Ignore {:?} from {:?}", cmd, source);
+                            emit_trigger_received(&app_handle, cmd, source, timestamp, false);
+                            continue;
+                        }
+                        Some(SourceAction::SwitchScene(scene)) => {
+                            switch_scene(&state, &scene).await;
+                            emit_trigger_received(&app_handle, cmd, source, timestamp, false);
+                            continue;
+                        }
+                        Some(SourceAction::Save) | None => {}
+                    }
+                    if !is_output_active_for_capture(&state).await {
+                        debug!("OBS is not streaming or recording, skip {:?}", cmd);
+                        let payload = CaptureSkippedPayload { cmd };
+                        EventSender::new(app_handle.clone()).emit(payload);
+                        emit_trigger_received(&app_handle, cmd, source, timestamp, false);
+                        continue;
+                    }
                     debug!("OBS fire!");
-                    let duration = {
-                        let sleep_dur = sleep_duration.read().unwrap();
-                        *sleep_dur
+                    emit_trigger_received(&app_handle, cmd, source, timestamp, true);
+                    let sender_skew_ms =
+                        mugi_schema::parse_sender_ts(&d).map(|ts| timestamp as i64 - ts as i64);
+                    if let Some(skew) = sender_skew_ms {
+                        info!("{:?} sender -> receiver skew: {}ms", cmd, skew);
+                    }
+                    let mut tags = mugi_schema::parse_tags(&d);
+                    if let Some(clock) = &game_clock {
+                        tags.insert("clock".to_string(), clock.clone());
+                    }
+                    if sequence_matched && let Some(sequence) = &sequence {
+                        tags.insert("sequence".to_string(), sequence.label());
+                    }
+                    {
+                        let mut history = state.trigger_history.lock().unwrap();
+                        if history.len() >= MAX_TRIGGER_HISTORY {
+                            history.pop_front();
+                        }
+                        history.push_back(TriggerHistoryEntry {
+                            timestamp,
+                            cmd,
+                            source,
+                            tags: tags.clone(),
+                        });
+                    }
+                    let screenshot_mode = state
+                        .capture_screenshot_on
+                        .read()
+                        .unwrap()
+                        .get(&cmd)
+                        .copied();
+                    if let Some(mode) = screenshot_mode {
+                        // 遅延は待たずにイベントの瞬間を撮る
+                        tokio::spawn(capture_screenshot(state.clone(), cmd, timestamp));
+                        if mode == ScreenshotMode::Instead {
+                            spawn_trigger_scene(&state, cmd);
+                            continue;
+                        }
+                    }
+                    let mut trigger = Trigger {
+                        cmd,
+                        received_at,
+                        timestamp,
+                        port,
+                        tags,
+                        combo: None,
+                        sender_skew_ms,
+                    };
+                    let capture_mode = *state.capture_mode.read().unwrap();
+                    let combo_window =
+                        std::time::Duration::from_secs(*state.combo_window_sec.read().unwrap());
+                    let combo_enabled =
+                        !combo_window.is_zero() && capture_mode != CaptureMode::Continuous;
+                    // EpicSaveは待たずに保存しておき、直後のScoredのクリップにまとめる
+                    if cmd == MugiCmd::Scored
+                        && combo_enabled
+                        && let Some(candidate) = combo_candidate.take()
+                        && received_at.duration_since(candidate.received_at) <= combo_window
+                    {
+                        debug!("Combo: EpicSave followed by Scored");
+                        trigger.combo = Some(candidate);
+                    }
+                    let delay = match capture_mode {
+                        CaptureMode::Continuous => {
+                            add_session_marker(&state, &app_handle, &trigger);
+                            spawn_trigger_scene(&state, cmd);
+                            continue;
+                        }
+                        CaptureMode::FixedDelay => {
+                            let delay = fixed_delay(&state, cmd);
+                            // 保存にかかる時間を見込んで早めに保存する
+                            let compensation = latency_compensation(&state);
+                            if !compensation.is_zero() {
+                                debug!("Latency compensation: {:?}", compensation);
+                            }
+                            delay.saturating_sub(compensation)
+                        }
+                        CaptureMode::UntilNextEvent => {
+                            let timeout = {
+                                let timeout_sec = state.next_event_timeout_sec.read().unwrap();
+                                std::time::Duration::from_secs(*timeout_sec)
+                            };
+                            next_msg = wait_next_event(&mut rx, timeout, &mut last_heartbeat).await;
+                            // 待っている間に止められていたら保存しない
+                            if *state.is_capture_paused.lock().unwrap() {
+                                debug!("Capture paused while waiting, skip {:?}", cmd);
+                                continue;
+                            }
+                            std::time::Duration::ZERO
+                        }
+                    };
+                    // sleep+保存をタスク化して、停止時にキャンセルできるようにする
+                    let save_task = tokio::spawn(delayed_save(
+                        state.clone(),
+                        app_handle.clone(),
+                        trigger,
+                        delay,
+                    ));
+                    {
+                        let mut pending = state.pending_save.lock().unwrap();
+                        *pending = Some(save_task.abort_handle());
+                    }
+                    let saved = match save_task.await {
+                        Ok(saved) => saved,
+                        Err(e) => {
+                            if e.is_cancelled() {
+                                debug!("Delayed save was cancelled");
+                            }
+                            None
+                        }
                     };
-                    tokio::time::sleep(std::time::Duration::from_secs(duration)).await;
-                    if let Err(e) = obs.save_replay_buffer().await {
-                        error!("Failed to save replay buffer: {}", e);
+                    if cmd == MugiCmd::EpicSave
+                        && combo_enabled
+                        && let Some(path) = saved
+                    {
+                        combo_candidate = Some(ComboCandidate {
+                            received_at,
+                            timestamp,
+                            path,
+                        });
                     }
                 }
             }
@@ -255,33 +4853,773 @@ async fn run_main_system(
     Ok(())
 }
 
+// 次のイベントを受信するかタイムアウトするまで待つ
+// 受信したメッセージは呼び出し側で改めて処理できるように返す
+async fn wait_next_event(
+    rx: &mut mpsc::Receiver<UdpMessage>,
+    timeout: std::time::Duration,
+    last_heartbeat: &mut Option<tokio::time::Instant>,
+) -> Option<UdpMessage> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let d = tokio::time::timeout_at(deadline, rx.recv()).await.ok()??;
+        // time/boostのような定期送信は区切りにならないので読み飛ばす
+        match mugi_schema::parse_cmd(&d.data) {
+            Ok(MugiCmd::Heartbeat) => *last_heartbeat = Some(tokio::time::Instant::now()),
+            Ok(cmd) if cmd.is_periodic() => continue,
+            _ => return Some(d),
+        }
+    }
+}
+
+// 受信したメッセージか、ハートビートの途切れ
+// 途切れはUDPからは送れないようにメッセージとは別に渡す
+enum Incoming {
+    Udp(UdpMessage),
+    HeartbeatLost,
+}
+
+// ハートビートを受信していて、それが途切れたらHeartbeatLostを返す
+async fn recv_or_heartbeat_lost(
+    rx: &mut mpsc::Receiver<UdpMessage>,
+    state: &AppState,
+    last_heartbeat: &mut Option<tokio::time::Instant>,
+) -> Option<Incoming> {
+    let timeout_sec = *state.heartbeat_timeout_sec.read().unwrap();
+    let Some(last) = last_heartbeat.filter(|_| timeout_sec > 0) else {
+        return rx.recv().await.map(Incoming::Udp);
+    };
+    let deadline = last + std::time::Duration::from_secs(timeout_sec);
+    match tokio::time::timeout_at(deadline, rx.recv()).await {
+        Ok(msg) => msg.map(Incoming::Udp),
+        Err(_) => {
+            warn!("No heartbeat for {}s, firing matchEnded", timeout_sec);
+            *last_heartbeat = None;
+            Some(Incoming::HeartbeatLost)
+        }
+    }
+}
+
+// アクティブなプロファイルにイベントの遅延があればそれを使う
+// なければトリガー表の遅延、クリップの長さのpost、sleep_duration_secの順
+fn fixed_delay(state: &AppState, cmd: MugiCmd) -> std::time::Duration {
+    let active_profile = state.active_profile.read().unwrap();
+    let profile_delay = active_profile.as_ref().and_then(|name| {
+        let profiles = state.delay_profiles.read().unwrap();
+        profiles
+            .get(name)
+            .and_then(|profile| profile.get(&cmd).copied())
+    });
+    if let Some(delay_sec) = profile_delay {
+        return std::time::Duration::from_secs(delay_sec);
+    }
+    if let Some(&delay_ms) = state.trigger_delays_ms.read().unwrap().get(&cmd) {
+        return std::time::Duration::from_millis(delay_ms);
+    }
+    let delay_sec = {
+        let clip_profiles = state.clip_profiles.read().unwrap();
+        clip_profiles.get(&cmd).map(|profile| profile.post_secs)
+    };
+    std::time::Duration::from_secs(
+        delay_sec.unwrap_or_else(|| *state.sleep_duration_sec.read().unwrap()),
+    )
+}
+
+// トランジションの終了を待つ上限
+const MAX_TRANSITION_WAIT: std::time::Duration = std::time::Duration::from_secs(3);
+
+#[derive(Serialize, Clone)]
+struct TransitionDelayPayload {
+    cmd: MugiCmd,
+}
+
+impl AppEvent for TransitionDelayPayload {
+    const NAME: &'static str = "save-delayed-transition";
+}
+
+// トランジション中に保存するとアニメーションが映るので、終わるまで待つ
+async fn wait_transition_before_save(state: &AppState, app_handle: &AppHandle, cmd: MugiCmd) {
+    if !*state.transition_aware.read().unwrap() {
+        return;
+    }
+    let Some(obs) = current_obs(state) else {
+        return;
+    };
+    if !obs.is_in_transition() {
+        return;
+    }
+    debug!("Scene transition in progress, delay saving {:?}", cmd);
+    let payload = TransitionDelayPayload { cmd };
+    EventSender::new(app_handle.clone()).emit(payload);
+    if !obs.wait_transition_end(MAX_TRANSITION_WAIT).await {
+        warn!(
+            "Scene transition did not end within {:?}, saving anyway",
+            MAX_TRANSITION_WAIT
+        );
+    }
+}
+
+// 移したクリップのパスが返ってくるまで待つ上限（別のドライブならコピーになる）
+const REPLAY_REDIRECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+// トリガーごとの保存先があれば、次に保存されるリプレイをそこへ移すよう頼む
+// OBSの保存先は動作中のリプレイバッファには反映されないので、保存後に移す
+fn redirect_replay(state: &AppState, cmd: MugiCmd) -> Option<oneshot::Receiver<PathBuf>> {
+    // モックのリプレイは実在しない
+    if state.mock_obs.read().unwrap().is_some() {
+        return None;
+    }
+    let folder = state
+        .trigger_output_folders
+        .read()
+        .unwrap()
+        .get(&cmd)
+        .cloned()?;
+    let (tx, rx) = oneshot::channel();
+    *state.replay_redirect.lock().unwrap() = Some((NextReplay::MoveTo(folder), tx));
+    Some(rx)
+}
+
+// 移せていれば移した先のパスを返す
+async fn wait_redirected_replay(
+    state: &AppState,
+    saved: Result<PathBuf, String>,
+    redirected: Option<oneshot::Receiver<PathBuf>>,
+) -> Result<PathBuf, String> {
+    let result = match (saved, redirected) {
+        (Ok(path), Some(rx)) => match tokio::time::timeout(REPLAY_REDIRECT_TIMEOUT, rx).await {
+            Ok(Ok(moved)) => return Ok(moved),
+            _ => {
+                warn!("{:?} was not moved to the trigger output folder", path);
+                Ok(path)
+            }
+        },
+        (saved, _) => saved,
+    };
+    // 使われなかった頼みが次の保存に残らないようにする
+    state.replay_redirect.lock().unwrap().take();
+    result
+}
+
+// プログラムに出ているシーンを撮ってセッションフォルダに保存する
+async fn capture_screenshot(state: AppState, cmd: MugiCmd, timestamp: u64) {
+    let Some(obs) = current_obs(&state) else {
+        warn!("OBS is not connected, skip screenshot for {:?}", cmd);
+        return;
+    };
+    let folder = state
+        .session
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|session| session.folder());
+    let Some(folder) = folder else {
+        warn!("No capture session, skip screenshot for {:?}", cmd);
+        return;
+    };
+    if let Err(e) = std::fs::create_dir_all(&folder) {
+        error!("Failed to create session folder {:?}: {}", folder, e);
+        return;
+    }
+    let path = folder.join(format!("Screenshot_{:?}_{}.png", cmd, timestamp));
+    if let Err(e) = obs.save_program_screenshot(&path).await {
+        error!("{}", e);
+        return;
+    }
+    info!("Screenshot saved: {:?}", path);
+    add_session_clip(
+        &state,
+        SessionClip {
+            path,
+            kind: ClipKind::Image,
+            event: Some(cmd),
+            triggered_at: timestamp,
+            delay_ms: 0,
+        },
+    );
+}
+
+// source_actionsやトリガー表でシーン切り替えが指定されたトリガー
+// トリガーごとのシーンがあれば切り替える（呼び出し元を待たせない）
+fn spawn_trigger_scene(state: &AppState, cmd: MugiCmd) {
+    let trigger_scene = state.trigger_scenes.read().unwrap().get(&cmd).cloned();
+    if let Some(scene) = trigger_scene {
+        let state = state.clone();
+        tokio::spawn(async move { switch_scene(&state, &scene).await });
+    }
+}
+
+async fn switch_scene(state: &AppState, scene: &str) {
+    let Some(obs) = current_obs(state) else {
+        warn!("OBS is not connected, skip switching scene to {}", scene);
+        return;
+    };
+    let transition = state.scene_transition.read().unwrap().clone();
+    if let Err(e) = obs
+        .set_current_scene_with_transition(scene, transition.as_ref())
+        .await
+    {
+        error!("{}", e);
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct ComboCapturedPayload {
+    path: PathBuf,
+    // まとめたので消したEpicSaveのクリップ
+    replaced: PathBuf,
+    // EpicSave/Scoredの受信時刻 unix time(ms)
+    saved_at: u64,
+    scored_at: u64,
+}
+
+impl AppEvent for ComboCapturedPayload {
+    const NAME: &'static str = "combo-captured";
+}
+
+// まとめたクリップに含まれるので、ファイルも記録も消す
+fn discard_clip(state: &AppState, path: &Path) {
+    remove_clip_file(state, path);
+    state.created_clips.lock().unwrap().retain(|p| p != path);
+    if let Some(session) = state.session.lock().unwrap().as_mut() {
+        session.clips.retain(|clip| clip.path != path);
+    }
+}
+
+// delay待ってからリプレイを保存し、保存したクリップのパスを返す
+async fn delayed_save(
+    state: AppState,
+    app_handle: AppHandle,
+    mut trigger: Trigger,
+    delay: std::time::Duration,
+) -> Option<PathBuf> {
+    let cmd = trigger.cmd;
+    let received_at = trigger.received_at;
+    tokio::time::sleep(delay).await;
+    wait_transition_before_save(&state, &app_handle, cmd).await;
+    // 意図的に待った時間（UntilNextEventやトランジションの待ち時間も含む）
+    let intentional = received_at.elapsed();
+    // 待っている間に再接続されているかもしれないので、ここで取得する
+    let Some(obs) = current_obs(&state) else {
+        buffer_trigger(&state, trigger);
+        return None;
+    };
+    if !ensure_disk_space(&obs, &state, &app_handle).await {
+        return None;
+    }
+    let save_guard = state.save_lock.lock().await;
+    let redirected = redirect_replay(&state, cmd);
+    let before = obs.get_last_replay().await;
+    let timed_out = match obs.save_replay_buffer().await {
+        Ok(()) => false,
+        // 応答が遅れただけで保存はされているかもしれないので、溜めずに書き出しを待つ
+        Err(e @ obs::RequestError::Timeout(_)) => {
+            warn!("Failed to save replay buffer: {}", e);
+            true
+        }
+        Err(e) => {
+            // OBSが落ちている可能性があるので、再接続まで溜めておく
+            error!("Failed to save replay buffer: {}", e);
+            state.replay_redirect.lock().unwrap().take();
+            buffer_trigger(&state, trigger);
+            return None;
+        }
+    };
+
+    let latency = received_at.elapsed().saturating_sub(intentional);
+    debug!("Trigger latency: {:?}", latency);
+    {
+        let mut latencies = state.trigger_latencies.lock().unwrap();
+        if latencies.len() >= MAX_TRIGGER_LATENCIES {
+            latencies.pop_front();
+        }
+        latencies.push_back(TriggerLatency {
+            cmd,
+            port: trigger.port,
+            timestamp: unix_millis(),
+            latency_ms: latency.as_millis() as u64,
+            sender_skew_ms: trigger.sender_skew_ms,
+        });
+    }
+
+    // 移し終えるまでは次の保存をしない
+    let saved = obs.wait_for_new_replay(before).await;
+    let saved = wait_redirected_replay(&state, saved, redirected).await;
+    drop(save_guard);
+    // 保存前に切り替えるとポストロールに切り替えが映るので、書き出しの後にする
+    spawn_trigger_scene(&state, cmd);
+    let path = match saved {
+        Ok(path) => path,
+        Err(e) => {
+            error!("{}", e);
+            // 応答がなく保存もされていなければ、再接続まで溜めておく
+            if timed_out {
+                buffer_trigger(&state, trigger);
+            }
+            return None;
+        }
+    };
+    // クリップの長さの指定があれば、トリガーのpre秒前より前を切る
+    // モックのクリップは実在しないので切らない
+    let pre_secs = state
+        .clip_profiles
+        .read()
+        .unwrap()
+        .get(&cmd)
+        .map(|profile| profile.pre_secs)
+        .unwrap_or_default();
+    // EpicSaveの瞬間がバッファに残っていればこのクリップにまとめる
+    let combo = match trigger.combo.take() {
+        Some(combo) => {
+            let since_save = combo.received_at.elapsed();
+            let covered = obs
+                .get_replay_buffer_length()
+                .await
+                .is_ok_and(|sec| since_save < std::time::Duration::from_secs(sec));
+            if !covered {
+                info!("EpicSave is out of the replay buffer, keep separate clips");
+            }
+            covered.then_some(combo)
+        }
+        None => None,
+    };
+    if combo.is_some() {
+        trigger
+            .tags
+            .insert("combo".to_string(), "epicSave+scored".to_string());
+    }
+    if pre_secs > 0 && state.mock_obs.read().unwrap().is_none() {
+        // まとめたときはEpicSaveのpre秒前から残す
+        let combo_span = combo
+            .as_ref()
+            .map(|combo| received_at.duration_since(combo.received_at))
+            .unwrap_or_default();
+        let keep = intentional + combo_span + std::time::Duration::from_secs(pre_secs);
+        if let Err(e) = trim_clip_head(&path, keep).await {
+            warn!("Failed to trim {:?} to clip profile: {}", path, e);
+        }
+    }
+    track_created_clip(&state, path.clone());
+    let delay_ms = intentional.as_millis() as u64;
+    add_session_clip(
+        &state,
+        SessionClip {
+            path: path.clone(),
+            kind: ClipKind::Video,
+            event: Some(cmd),
+            triggered_at: trigger.timestamp,
+            delay_ms,
+        },
+    );
+    let sidecar = ClipSidecar {
+        event: Some(cmd),
+        timestamp: Some(trigger.timestamp),
+        delay_ms: Some(delay_ms),
+        tags: trigger.tags,
+        ..Default::default()
+    };
+    if let Err(e) = sidecar::write_sidecar(&path, &sidecar) {
+        error!("{}", e);
+    }
+    if let Some(combo) = combo {
+        discard_clip(&state, &combo.path);
+        let payload = ComboCapturedPayload {
+            path: path.clone(),
+            replaced: combo.path,
+            saved_at: combo.timestamp,
+            scored_at: trigger.timestamp,
+        };
+        EventSender::new(app_handle.clone()).emit(payload);
+    }
+    // モックのクリップは実在しないので変換しない
+    let transcode = state.transcode.read().unwrap().clone();
+    if let Some(settings) = transcode
+        && state.mock_obs.read().unwrap().is_none()
+    {
+        // 変換を待たずに次のトリガーを受け付ける
+        tokio::spawn(transcode_clip(
+            state.clone(),
+            app_handle.clone(),
+            path.clone(),
+            settings,
+        ));
+    }
+    if *state.instant_replay.read().unwrap() {
+        // 再生が終わるのを待たずに次のトリガーを受け付ける
+        tokio::spawn(play_instant_replay(
+            state.clone(),
+            app_handle.clone(),
+            path.clone(),
+        ));
+    }
+    let webhook_url = state.webhook_url.read().unwrap().clone();
+    if let Some(url) = webhook_url {
+        let payload = WebhookPayload {
+            event: cmd,
+            path: path.clone(),
+            timestamp: unix_millis(),
+        };
+        webhook::notify(url, payload, EventSender::new(app_handle));
+    }
+    Some(path)
+}
+
+// 書き出し直後はまだ読めないことがあるので、読めるようになるまで待つ間隔と回数
+const CLIP_READABLE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+const CLIP_READABLE_POLL_ATTEMPTS: u32 = 25;
+
+async fn wait_clip_readable(path: &Path) -> bool {
+    for _ in 0..CLIP_READABLE_POLL_ATTEMPTS {
+        if is_clip_readable(path) {
+            return true;
+        }
+        tokio::time::sleep(CLIP_READABLE_POLL_INTERVAL).await;
+    }
+    false
+}
+
+// 通常の再生と同じ設定（シーンや再生後の動作）で保存したクリップを再生する
+async fn play_instant_replay(state: AppState, app_handle: AppHandle, path: PathBuf) {
+    // モックのクリップは実在しない
+    let is_mock = state.mock_obs.read().unwrap().is_some();
+    if !is_mock && !wait_clip_readable(&path).await {
+        warn!("{:?} is not readable, skip instant replay", path);
+        return;
+    }
+    info!("Instant replay: {:?}", path);
+    if let Err(e) = play_clips(&state, app_handle, std::slice::from_ref(&path)).await {
+        error!("Failed to play instant replay: {}", e);
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct ClipTranscodedPayload {
+    original: PathBuf,
+    path: PathBuf,
+    // 元のクリップを置き換えたか
+    replaced: bool,
+}
+
+impl AppEvent for ClipTranscodedPayload {
+    const NAME: &'static str = "clip-transcoded";
+}
+
+// 保存したクリップを設定に従って変換する
+async fn transcode_clip(
+    state: AppState,
+    app_handle: AppHandle,
+    path: PathBuf,
+    settings: TranscodeSettings,
+) {
+    if !wait_clip_readable(&path).await {
+        warn!("{:?} is not readable, skip transcoding", path);
+        return;
+    }
+    let _transcode_guard = state.transcode_lock.lock().await;
+    let output = ffmpeg::transcoded_path(&path, &settings.container);
+    if let Err(e) = ffmpeg::transcode(
+        &path,
+        &output,
+        &settings.video_codec,
+        settings.video_bitrate_kbps,
+    )
+    .await
+    {
+        let _ = std::fs::remove_file(&output);
+        error!("Failed to transcode {:?}: {}", path, e);
+        return;
+    }
+    // すぐ再生する設定だと再生中の元のクリップを消してしまうので置き換えない
+    let instant_replay = *state.instant_replay.read().unwrap();
+    if settings.replace_original && instant_replay {
+        info!("Instant replay is on, keep original clip {:?}", path);
+    }
+    let (transcoded, replaced) = if settings.replace_original && !instant_replay {
+        match replace_with_transcoded(&state, &path, &output) {
+            Ok(transcoded) => (transcoded, true),
+            Err(e) => {
+                // 変換したファイルは残す
+                warn!("{}", e);
+                (output, false)
+            }
+        }
+    } else {
+        (output, false)
+    };
+    info!("Transcoded {:?} -> {:?}", path, transcoded);
+    let payload = ClipTranscodedPayload {
+        original: path,
+        path: transcoded,
+        replaced,
+    };
+    EventSender::new(app_handle).emit(payload);
+}
+
+// 元のクリップを消して、変換後のファイルを元の名前（拡張子は変換後のもの）にする
+// sidecarは拡張子違いの同じ名前なのでそのまま使える
+fn replace_with_transcoded(
+    state: &AppState,
+    original: &Path,
+    transcoded: &Path,
+) -> Result<PathBuf, String> {
+    let extension = transcoded.extension().unwrap_or_default();
+    let path = original.with_extension(extension);
+    std::fs::remove_file(original)
+        .map_err(|e| format!("Failed to remove original clip {:?}: {e}", original))?;
+    std::fs::rename(transcoded, &path)
+        .map_err(|e| format!("Failed to replace clip {:?}: {e}", original))?;
+    if path != original {
+        rename_tracked_clip(state, original, &path);
+    }
+    Ok(path)
+}
+
+// 一覧・削除対象・セッションの記録に残っている古いパスを差し替える
+fn rename_tracked_clip(state: &AppState, old: &Path, new: &Path) {
+    for clip in state.clips.lock().unwrap().iter_mut() {
+        if clip.as_path() == old {
+            *clip = new.to_path_buf();
+        }
+    }
+    for clip in state.created_clips.lock().unwrap().iter_mut() {
+        if clip.as_path() == old {
+            *clip = new.to_path_buf();
+        }
+    }
+    if let Some(session) = state.session.lock().unwrap().as_mut() {
+        for clip in session.clips.iter_mut() {
+            if clip.path == old {
+                clip.path = new.to_path_buf();
+            }
+        }
+    }
+}
+
+// 最後のkeep分だけを残して元のファイルを置き換える（keepより短ければそのまま）
+async fn trim_clip_head(path: &Path, keep: std::time::Duration) -> Result<(), String> {
+    let duration_sec = ffmpeg::probe_duration(path).await?;
+    let keep_sec = keep.as_secs_f64();
+    if duration_sec <= keep_sec {
+        return Ok(());
+    }
+    let output = ffmpeg::trimmed_path(path);
+    if let Err(e) = ffmpeg::trim(
+        path,
+        &output,
+        duration_sec - keep_sec,
+        duration_sec,
+        false,
+        |_| {},
+    )
+    .await
+    {
+        let _ = std::fs::remove_file(&output);
+        return Err(e);
+    }
+    std::fs::rename(&output, path).map_err(|e| format!("Failed to replace clip: {e}"))
+}
+
+// tokio-consoleの待ち受けアドレス（console-subscriberの既定値）
+const TOKIO_CONSOLE_DEFAULT_ADDR: &str = "127.0.0.1:6669";
+
+// 診断用なので、使えなくても警告だけ出して起動を続ける
+fn init_console_subscriber() {
+    let addr = std::env::var("TOKIO_CONSOLE_BIND")
+        .unwrap_or_else(|_| TOKIO_CONSOLE_DEFAULT_ADDR.to_string());
+    // 使用中だとsubscriberのスレッドがpanicするので先に確認する
+    if let Err(e) = std::net::TcpListener::bind(&addr) {
+        warn!("tokio-console is disabled, failed to bind {}: {}", addr, e);
+        return;
+    }
+    // グローバルなsubscriberが既に設定されているとpanicする
+    if std::panic::catch_unwind(console_subscriber::init).is_err() {
+        warn!("tokio-console is disabled, failed to initialize console subscriber");
+    }
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    console_subscriber::init();
+    let state = AppState::new();
+    let recent_logs = state.recent_logs.clone();
 
     tauri::Builder::default()
         .plugin(
             tauri_plugin_log::Builder::new()
                 .target(Target::new(TargetKind::Folder {
-                    path: std::path::PathBuf::from("./logs"),
+                    path: std::path::PathBuf::from(LOG_DIR),
                     file_name: None,
                 }))
+                // 整形済み(時刻・レベル付き)の行をリングバッファに溜める
+                .target(Target::new(TargetKind::Dispatch(
+                    tauri_plugin_log::fern::Dispatch::new().chain(
+                        tauri_plugin_log::fern::Output::call(move |record| {
+                            let mut recent_logs = recent_logs.lock().unwrap();
+                            if recent_logs.len() >= MAX_RECENT_LOG_LINES {
+                                recent_logs.pop_front();
+                            }
+                            recent_logs.push_back(record.args().to_string());
+                        }),
+                    ),
+                )))
                 .level(log::LevelFilter::Debug)
+                // set_obs_traceで有効にしたときだけ出力される
+                .level_for("tuari_template_lib::obs", log::LevelFilter::Trace)
                 .build(),
         )
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_opener::init())
+        .register_asynchronous_uri_scheme_protocol(WINDOW_CLIP_SCHEME, |ctx, request, responder| {
+            let state = ctx.app_handle().state::<AppState>().inner().clone();
+            tauri::async_runtime::spawn(async move {
+                responder.respond(serve_window_clip(&state, &request).await);
+            });
+        })
+        .on_window_event(|window, event| {
+            // ×で閉じられたときも読めなくする
+            if window.label() == HIGHLIGHT_WINDOW_LABEL
+                && let tauri::WindowEvent::Destroyed = event
+            {
+                clear_window_playback(&window.state::<AppState>());
+            }
+        })
         .setup(|app| {
+            // ロガー初期化後に読むためsetupで適用する
+            init_console_subscriber();
+            // 自動接続が接続待ちを使うので、spawnする前に読む
+            app.state::<AppState>().apply_preferences();
+            app.state::<AppState>().apply_env_config();
+            match app.path().app_data_dir() {
+                Ok(dir) => {
+                    let ui_preferences = UiPreferences::load(dir.clone());
+                    *app.state::<AppState>().ui_preferences.lock().unwrap() = ui_preferences;
+                    let playlists = NamedPlaylists::load(dir);
+                    *app.state::<AppState>().playlists.lock().unwrap() = playlists;
+                }
+                Err(e) => warn!("Failed to resolve app data dir: {}", e),
+            }
             let handle = app.handle().clone();
+            tauri::async_runtime::spawn(autoconnect(handle.clone()));
             tauri::async_runtime::spawn(async move {
                 update(handle).await.unwrap();
             });
             Ok(())
         })
-        .manage(AppState::new())
+        .manage(state)
         .invoke_handler(tauri::generate_handler![
             connect_obs,
             play_highlights,
+            play_single_clip,
+            play_highlights_dual,
+            play_highlights_window,
+            create_playlist,
+            select_playlist,
+            play_playlist,
+            list_playlists,
+            append_to_playback,
+            window_next,
+            window_stop,
+            save_replay_now,
+            benchmark_save,
+            capture_clip,
+            get_filename_formatting,
+            get_effective_output_info,
+            check_output_writable,
+            normalize_directory,
+            get_replay_output_format,
+            set_replay_output_format,
+            get_clip_tags,
+            set_clip_tags,
+            mark_highlight_peak,
+            get_suggested_delay,
+            set_filename_formatting,
+            pause_capture,
+            resume_capture,
+            cancel_pending_save,
+            set_udp_processing,
+            disconnect_obs,
+            set_shutdown_timeout,
+            set_min_buffer_length,
+            reconnect_obs,
+            set_mock_obs,
+            set_obs_request_timeout,
+            set_obs_trace,
+            update_obs_password,
+            set_pending_trigger_policy,
+            get_system_status,
+            get_app_info,
+            set_webhook_url,
+            set_trigger_enabled,
+            get_trigger_enabled,
+            set_trigger_sequence,
+            list_supported_commands,
+            parse_command_preview,
+            set_playback_audio_mode,
+            trim_clip,
+            verify_clip,
+            cancel_export,
+            get_trigger_latencies,
+            set_capture_mode,
+            stop_playback,
+            set_highlights_scene,
+            set_highlight_fit,
+            set_playback_transform,
+            set_playback_buffering,
+            set_autoconnect,
+            save_credentials,
+            load_credentials,
+            delete_credentials,
+            set_startup_delay,
+            set_require_active_output,
+            export_trigger_history,
+            get_adaptive_timing,
+            set_adaptive_timing,
+            set_latency_override,
+            get_obs_stats,
+            get_obs_output_status,
+            start_virtual_cam,
+            stop_virtual_cam,
+            get_preference,
+            set_preference,
+            set_scene_transition,
+            set_playback_loop,
+            set_playback_freeze_intro,
+            set_hide_when_idle,
+            set_max_clips,
+            get_session_manifest,
+            get_session_markers,
+            purge_session_clips,
+            set_combo_window,
+            set_heartbeat_timeout,
+            set_connection_flap_threshold,
+            get_session_stats,
+            reset_to_defaults,
+            get_recent_logs,
+            start_recording_triggers,
+            stop_recording_triggers,
+            replay_triggers,
+            run_demo,
+            set_source_action,
+            add_udp_listener,
+            remove_udp_listener,
+            get_local_addresses,
+            save_profile,
+            set_active_profile,
+            list_profiles,
+            set_clip_profile,
+            get_trigger_table,
+            set_trigger_table,
+            set_trigger_output_folder,
+            set_capture_screenshot_on,
+            set_min_free_space,
+            set_transcode_settings,
+            set_disk_full_policy,
+            set_udp_framing,
+            set_channel_capacity,
+            get_channel_drops,
+            set_transition_aware,
+            set_instant_replay,
+            set_obs_profile,
+            set_obs_scene_collection,
             set_sleep_duration,
             get_sleep_duration
         ])
@@ -308,3 +5646,47 @@ async fn update(app: AppHandle) -> tauri_plugin_updater::Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_busy_guard_concurrent() {
+        let flag = Arc::new(Mutex::new(false));
+        let barrier = Arc::new(std::sync::Barrier::new(8));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let flag = flag.clone();
+                let barrier = barrier.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    BusyGuard::acquire(&flag)
+                })
+            })
+            .collect();
+        let guards: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(guards.iter().filter(|g| g.is_some()).count(), 1);
+        assert!(*flag.lock().unwrap());
+
+        // 先の接続が終われば（失敗しても）次の接続を受け付ける
+        drop(guards);
+        assert!(!*flag.lock().unwrap());
+        assert!(BusyGuard::acquire(&flag).is_some());
+    }
+
+    #[test]
+    fn test_window_clip_request() {
+        assert_eq!(
+            percent_decode("C%3A%5Cclips%5Creplay%201.mkv").as_deref(),
+            Some("C:\\clips\\replay 1.mkv")
+        );
+        assert_eq!(percent_decode("%2"), None);
+
+        assert_eq!(parse_byte_range("bytes=0-", 100), Some((0, 99)));
+        assert_eq!(parse_byte_range("bytes=10-1000", 100), Some((10, 99)));
+        assert_eq!(parse_byte_range("bytes=100-", 100), None);
+        assert_eq!(parse_byte_range("bytes=-50", 100), None);
+        assert_eq!(parse_byte_range("bytes=0-", 0), None);
+    }
+}
@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::{debug, info};
+use tokio::sync::Notify;
+use tokio::sync::mpsc::Sender;
+
+// 架空のリプレイの保存先（実際には書き出さない）
+pub const MOCK_REPLAY_DIR: &str = "./mock_replays";
+// 架空の再生にかかる時間（1クリップあたり）
+const MOCK_CLIP_DURATION: Duration = Duration::from_secs(3);
+// 架空の空き容量
+pub const MOCK_FREE_SPACE_MB: u64 = 1024 * 1024;
+// 架空のリプレイバッファの長さ
+pub const MOCK_BUFFER_LENGTH_SEC: u64 = 20;
+// 架空のキャンバスサイズ
+pub const MOCK_CANVAS_SIZE: (f32, f32) = (1920.0, 1080.0);
+
+// OBSなしでトリガーからの流れを確認するための偽物
+// Obsのインスタンスをまたいで共有する
+#[derive(Default)]
+pub struct MockObs {
+    last_replay: Mutex<Option<PathBuf>>,
+    replay_tx: Mutex<Option<Sender<PathBuf>>>,
+    playing_clips: Mutex<usize>,
+    stopped: Notify,
+}
+
+impl MockObs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_replay_listener(&self, tx: Sender<PathBuf>) {
+        *self.replay_tx.lock().unwrap() = Some(tx);
+    }
+
+    // ReplayBufferSavedイベントと同じように架空のパスを流す
+    pub fn save_replay(&self) {
+        let millis = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let path = PathBuf::from(MOCK_REPLAY_DIR).join(format!("Replay_{millis}.mkv"));
+        info!("Mock OBS saved replay: {:?}", path);
+        *self.last_replay.lock().unwrap() = Some(path.clone());
+        let tx = self.replay_tx.lock().unwrap().clone();
+        if let Some(tx) = tx
+            && let Err(e) = tx.try_send(path)
+        {
+            debug!("Mock OBS failed to send replay path: {}", e);
+        }
+    }
+
+    pub fn last_replay(&self) -> Option<PathBuf> {
+        self.last_replay.lock().unwrap().clone()
+    }
+
+    pub fn play(&self, clips: usize) {
+        info!("Mock OBS playing {} clips", clips);
+        *self.playing_clips.lock().unwrap() = clips;
+    }
+
+    pub fn stop(&self) {
+        self.stopped.notify_waiters();
+    }
+
+    // 全クリップ分の時間が経つか、stopされるまで待つ
+    pub async fn wait_playback_end(&self) {
+        let clips = *self.playing_clips.lock().unwrap();
+        let duration = MOCK_CLIP_DURATION * clips as u32;
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => {}
+            _ = self.stopped.notified() => {}
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use anyhow::{Result, anyhow};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum MugiCmd {
     Init,
     EndReplay,
@@ -23,6 +25,10 @@ pub enum MugiCmd {
     SubScore,
     Score,
     Player,
+    // 送信側が生きていることを知らせる（送らない送信側もある）
+    Heartbeat,
+    // ハートビートが途切れたときに内部で発行する
+    MatchEnded,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq)]
@@ -112,33 +118,118 @@ struct MugiData<T> {
     data: Option<T>,
 }
 
+impl MugiCmd {
+    // UDPで受け付けるコマンド（MatchEndedは内部でのみ発行するので含めない）
+    pub const ALL: [MugiCmd; 21] = [
+        MugiCmd::Init,
+        MugiCmd::EndReplay,
+        MugiCmd::EndStats,
+        MugiCmd::TeamNames,
+        MugiCmd::Demolished,
+        MugiCmd::Scored,
+        MugiCmd::MatchId,
+        MugiCmd::Start,
+        MugiCmd::End,
+        MugiCmd::Stats,
+        MugiCmd::Goals,
+        MugiCmd::EpicSave,
+        MugiCmd::Dbg,
+        MugiCmd::DisplayNames,
+        MugiCmd::PlayerTable,
+        MugiCmd::Time,
+        MugiCmd::Boost,
+        MugiCmd::SubScore,
+        MugiCmd::Score,
+        MugiCmd::Player,
+        MugiCmd::Heartbeat,
+    ];
+
+    // UDPで送られてくるコマンド名
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MugiCmd::Init => "init",
+            MugiCmd::EndReplay => "endReplay",
+            MugiCmd::EndStats => "endStats",
+            MugiCmd::TeamNames => "teamNames",
+            MugiCmd::Demolished => "demolished",
+            MugiCmd::Scored => "scored",
+            MugiCmd::MatchId => "matchId",
+            MugiCmd::Start => "start",
+            MugiCmd::End => "end",
+            MugiCmd::Stats => "stats",
+            MugiCmd::Goals => "goals",
+            MugiCmd::EpicSave => "epicSave",
+            MugiCmd::Dbg => "dbg",
+            MugiCmd::DisplayNames => "displayNames",
+            MugiCmd::PlayerTable => "playerTable",
+            MugiCmd::Time => "time",
+            MugiCmd::Boost => "boost",
+            MugiCmd::SubScore => "subScore",
+            MugiCmd::Score => "score",
+            MugiCmd::Player => "player",
+            MugiCmd::Heartbeat => "heartbeat",
+            MugiCmd::MatchEnded => "matchEnded",
+        }
+    }
+
+    // 試合中に定期的に送られてくるコマンド
+    pub fn is_periodic(&self) -> bool {
+        matches!(self, MugiCmd::Time | MugiCmd::Boost | MugiCmd::Heartbeat)
+    }
+}
+
+impl FromStr for MugiCmd {
+    type Err = anyhow::Error;
+
+    // 前後の空白は無視する（大文字小文字は区別する）
+    fn from_str(cmd: &str) -> Result<Self> {
+        let cmd = cmd.trim();
+        MugiCmd::ALL
+            .into_iter()
+            .find(|mugi_cmd| mugi_cmd.as_str() == cmd)
+            .ok_or_else(|| anyhow!("mugi parse failed"))
+    }
+}
+
 pub fn parse_cmd(json: &str) -> Result<MugiCmd> {
     let data: MugiData<serde_json::Value> = serde_json::from_str(json)?;
-    let cmd = data.cmd.as_str();
-    let mugi_cmd = match cmd {
-        "init" => MugiCmd::Init,
-        "endReplay" => MugiCmd::EndReplay,
-        "endStats" => MugiCmd::EndStats,
-        "teamNames" => MugiCmd::TeamNames,
-        "demolished" => MugiCmd::Demolished,
-        "scored" => MugiCmd::Scored,
-        "matchId" => MugiCmd::MatchId,
-        "start" => MugiCmd::Start,
-        "end" => MugiCmd::End,
-        "stats" => MugiCmd::Stats,
-        "goals" => MugiCmd::Goals,
-        "epicSave" => MugiCmd::EpicSave,
-        "dbg" => MugiCmd::Dbg,
-        "displayNames" => MugiCmd::DisplayNames,
-        "playerTable" => MugiCmd::PlayerTable,
-        "time" => MugiCmd::Time,
-        "boost" => MugiCmd::Boost,
-        "subScore" => MugiCmd::SubScore,
-        "score" => MugiCmd::Score,
-        "player" => MugiCmd::Player,
-        _ => return Err(anyhow!("mugi parse failed")),
+    data.cmd.parse()
+}
+
+// 送信側の時刻 unix time(ms)。遅延の計測用で、送らない送信側もある
+// 数値でなければ無視する（コマンド自体は受け付ける）
+pub fn parse_sender_ts(json: &str) -> Option<u64> {
+    serde_json::from_str::<serde_json::Value>(json)
+        .ok()?
+        .get("ts")?
+        .as_u64()
+}
+
+// 同じイベントの再送を見分けるためのID（送らない送信側もある）
+pub fn parse_event_id(json: &str) -> Option<String> {
+    let mut tags = parse_tags(json);
+    ["eventId", "id"]
+        .into_iter()
+        .find_map(|key| tags.remove(key))
+}
+
+// dataのうち文字列・数値・真偽値の項目をタグとして取り出す
+pub fn parse_tags(json: &str) -> HashMap<String, String> {
+    let Ok(data) = serde_json::from_str::<MugiData<serde_json::Value>>(json) else {
+        return HashMap::new();
+    };
+    let Some(serde_json::Value::Object(fields)) = data.data else {
+        return HashMap::new();
     };
-    Ok(mugi_cmd)
+    fields
+        .into_iter()
+        .filter_map(|(key, value)| match value {
+            serde_json::Value::String(s) => Some((key, s)),
+            serde_json::Value::Number(n) => Some((key, n.to_string())),
+            serde_json::Value::Bool(b) => Some((key, b.to_string())),
+            _ => None,
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -168,6 +259,124 @@ mod test {
     //             "Player_Bot_Bandit".to_string(),];
     //     assert_eq!(msg.data,expect);
     // }
+    const WIRE_NAMES: [(&str, MugiCmd); 21] = [
+        ("init", MugiCmd::Init),
+        ("endReplay", MugiCmd::EndReplay),
+        ("endStats", MugiCmd::EndStats),
+        ("teamNames", MugiCmd::TeamNames),
+        ("demolished", MugiCmd::Demolished),
+        ("scored", MugiCmd::Scored),
+        ("matchId", MugiCmd::MatchId),
+        ("start", MugiCmd::Start),
+        ("end", MugiCmd::End),
+        ("stats", MugiCmd::Stats),
+        ("goals", MugiCmd::Goals),
+        ("epicSave", MugiCmd::EpicSave),
+        ("dbg", MugiCmd::Dbg),
+        ("displayNames", MugiCmd::DisplayNames),
+        ("playerTable", MugiCmd::PlayerTable),
+        ("time", MugiCmd::Time),
+        ("boost", MugiCmd::Boost),
+        ("subScore", MugiCmd::SubScore),
+        ("score", MugiCmd::Score),
+        ("player", MugiCmd::Player),
+        ("heartbeat", MugiCmd::Heartbeat),
+    ];
+
+    #[test]
+    fn test_parse_cmd_all_variants() {
+        for (name, expect) in WIRE_NAMES {
+            let msg = format!(r#"{{"cmd":"{name}"}}"#);
+            assert_eq!(parse_cmd(&msg).unwrap(), expect, "{name}");
+            let msg = format!(r#"{{"cmd":"{name}","data":null}}"#);
+            assert_eq!(parse_cmd(&msg).unwrap(), expect, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_all_round_trip() {
+        assert_eq!(MugiCmd::ALL.len(), WIRE_NAMES.len());
+        for cmd in MugiCmd::ALL {
+            assert_eq!(cmd.as_str().parse::<MugiCmd>().unwrap(), cmd);
+        }
+    }
+
+    #[test]
+    fn test_parse_cmd_rejects_internal() {
+        assert!(parse_cmd(r#"{"cmd":"matchEnded"}"#).is_err());
+        assert!("matchEnded".parse::<MugiCmd>().is_err());
+    }
+
+    #[test]
+    fn test_parse_cmd_case_sensitive() {
+        for (name, _) in WIRE_NAMES {
+            let msg = format!(r#"{{"cmd":"{}"}}"#, name.to_uppercase());
+            assert!(parse_cmd(&msg).is_err(), "{name}");
+        }
+        assert!(parse_cmd(r#"{"cmd":"EpicSave"}"#).is_err());
+        assert!(parse_cmd(r#"{"cmd":"epicsave"}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_cmd_whitespace() {
+        assert_eq!(parse_cmd(r#"{"cmd":" goals "}"#).unwrap(), MugiCmd::Goals);
+        assert_eq!(
+            parse_cmd(r#"{"cmd":"\tepicSave\n"}"#).unwrap(),
+            MugiCmd::EpicSave
+        );
+        assert_eq!(
+            parse_cmd(" \r\n{\"cmd\":\"scored\"}\r\n ").unwrap(),
+            MugiCmd::Scored
+        );
+        assert!(parse_cmd(r#"{"cmd":"epic Save"}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_cmd_empty() {
+        assert!(parse_cmd("").is_err());
+        assert!(parse_cmd("   ").is_err());
+        assert!(parse_cmd("{}").is_err());
+        assert!(parse_cmd(r#"{"cmd":""}"#).is_err());
+        assert!(parse_cmd(r#"{"cmd":"   "}"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_cmd_malformed() {
+        assert!(parse_cmd("goals").is_err());
+        assert!(parse_cmd(r#"{"cmd":"goals""#).is_err());
+        assert!(parse_cmd(r#"{"cmd":null}"#).is_err());
+        assert!(parse_cmd(r#"{"cmd":1}"#).is_err());
+        assert!(parse_cmd(r#"{"cmd":["goals"]}"#).is_err());
+        assert!(parse_cmd(r#"["goals"]"#).is_err());
+        assert!(parse_cmd(r#"{"command":"goals"}"#).is_err());
+        assert!(parse_cmd(r#"{"cmd":"unknown"}"#).is_err());
+        assert!(parse_cmd("\u{0}").is_err());
+    }
+
+    #[test]
+    fn test_parse_sender_ts() {
+        let msg = r#"{"cmd":"goals","ts":1700000000000}"#;
+        assert_eq!(parse_cmd(msg).unwrap(), MugiCmd::Goals);
+        assert_eq!(parse_sender_ts(msg), Some(1700000000000));
+        assert_eq!(parse_sender_ts(r#"{"cmd":"goals"}"#), None);
+        assert_eq!(parse_sender_ts(r#"{"cmd":"goals","ts":"now"}"#), None);
+        assert_eq!(
+            parse_cmd(r#"{"cmd":"goals","ts":"now"}"#).unwrap(),
+            MugiCmd::Goals
+        );
+        assert_eq!(parse_sender_ts(r#"{"cmd":"goals","ts":-1}"#), None);
+    }
+
+    #[test]
+    fn test_parse_event_id() {
+        let msg = r#"{"cmd":"scored","data":{"eventId":"a1","id":7}}"#;
+        assert_eq!(parse_event_id(msg), Some("a1".to_string()));
+        let msg = r#"{"cmd":"scored","data":{"id":7}}"#;
+        assert_eq!(parse_event_id(msg), Some("7".to_string()));
+        assert_eq!(parse_event_id(r#"{"cmd":"scored"}"#), None);
+        assert_eq!(parse_event_id(r#"{"cmd":"scored","data":{"id":{}}}"#), None);
+    }
+
     #[test]
     fn test_all() {
         use std::fs::read_to_string;
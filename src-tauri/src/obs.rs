@@ -1,18 +1,238 @@
-use std::path::PathBuf;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
 
+use base64::{Engine, engine::general_purpose::STANDARD};
 use futures_util::{StreamExt, pin_mut};
-use log::debug;
-use obws::{Client, events::Event, requests::custom::source_settings::SlideshowFile};
+use log::{debug, error, trace, warn};
+use obws::{
+    Client,
+    client::ConnectConfig,
+    common::{Alignment, BoundsType, MediaAction, MonitorType},
+    events::{Event, OutputState},
+    requests::custom::source_settings::SlideshowFile,
+    requests::inputs::InputId,
+    requests::scene_items::{Bounds, Position, SceneItemTransform},
+    requests::sources::{SourceId, TakeScreenshot},
+    responses::media_inputs::MediaState,
+};
+use serde::{Deserialize, Serialize};
 use tokio::sync::{OnceCell, mpsc::Sender};
+use tokio::task::JoinHandle;
 
 use time::Duration;
-const UNIQUE_REPLAY_SOURCE_NAME: &str = "RL_REPLAY_VLC_SOURCE";
+
+use crate::events::{AppEvent, OverflowReporter};
+use crate::mock_obs::{self, MockObs};
+pub const UNIQUE_REPLAY_SOURCE_NAME: &str = "RL_REPLAY_VLC_SOURCE";
+pub const DEFAULT_REQUEST_TIMEOUT_SEC: u64 = 10;
+// 再生が始まるのを待つ間隔と回数
+const PLAYING_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+const PLAYING_POLL_ATTEMPTS: u32 = 100;
+// 再生が始まらないまま（読み込みの失敗など）待つ上限
+const PLAYBACK_START_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+// 再生の終了を確認する間隔
+const PLAYBACK_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+// トランジションの終了を確認する間隔
+const TRANSITION_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+// トレースログで値を伏せるフィールド名（部分一致、小文字）
+const SECRET_FIELDS: [&str; 4] = ["password", "key", "token", "auth"];
+
+// OBSへのリクエストのエラー（応答がないのか、OBSがエラーを返したのかを区別する）
+#[derive(Debug)]
+pub enum RequestError {
+    NotConnected,
+    Timeout(std::time::Duration),
+    Obs(obws::error::Error),
+}
+
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::NotConnected => write!(f, "failed to get client"),
+            RequestError::Timeout(timeout) => {
+                write!(f, "OBS request timed out after {:?}", timeout)
+            }
+            RequestError::Obs(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+// ハイライト再生中のVLCソースの音声
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum PlaybackAudioMode {
+    Normal,
+    MonitorOnly,
+    Muted,
+}
+
+// 再生開始時にVLCソースをキャンバスに合わせる方法
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum HighlightFit {
+    // アスペクト比を保ってキャンバス内に収める
+    Fit,
+    // アスペクト比を保ってキャンバスを埋める
+    Fill,
+    // キャンバスに引き伸ばす
+    Stretch,
+    // OBS側の設定のまま
+    None,
+}
+
+// ソースの位置とサイズ（キャンバス座標、左上基準）
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SourceTransform {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl SourceTransform {
+    // キャンバスからはみ出していないか確認する
+    pub fn validate(&self, canvas_width: f32, canvas_height: f32) -> Result<(), String> {
+        if self.width <= 0.0 || self.height <= 0.0 {
+            return Err("幅と高さは0より大きくしてください".to_string());
+        }
+        if self.x < 0.0
+            || self.y < 0.0
+            || self.x + self.width > canvas_width
+            || self.y + self.height > canvas_height
+        {
+            return Err(format!(
+                "キャンバス({}x{})の範囲外です",
+                canvas_width, canvas_height
+            ));
+        }
+        Ok(())
+    }
+}
+
+// ハイライト再生の設定
+pub struct PlaybackOptions {
+    pub highlights_scene: Option<String>,
+    pub fit: HighlightFit,
+    // 指定があればfitより優先してこの位置・サイズで再生する（PiP用）
+    pub transform: Option<SourceTransform>,
+    // プレイリストを停止されるまで繰り返す
+    pub loop_playlist: bool,
+    pub transition: Option<SceneTransition>,
+    // VLCソースのキャッシュ時間
+    pub caching_ms: u64,
+}
+
+// VLCソース1つ分のプレイリスト
+pub struct VlcPlaylist<'a> {
+    pub source: &'a str,
+    pub clips: &'a [PathBuf],
+    // Noneならoptionsのfitに従う
+    pub transform: Option<SourceTransform>,
+}
+
+// 再生し終えるはずの時刻。再生中にクリップを足したら延ばせるように共有する
+// Noneなら期限なし（ループ再生や長さが分からないとき）
+pub type PlaybackDeadline = Arc<Mutex<Option<tokio::time::Instant>>>;
+
+#[derive(Default)]
+pub struct PlaybackStarted {
+    // highlights_sceneに切り替える前のシーン名
+    pub original_scene: Option<String>,
+    // 消されていたVLCソースを作り直した
+    pub recreated_source: bool,
+}
+
+// シーン切り替え時のトランジション
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SceneTransition {
+    // OBSのトランジション名 (例: "Fade")
+    pub name: String,
+    pub duration_ms: u64,
+}
+
+// OBSの各出力が動いているか
+#[derive(Serialize, Debug, Clone, Copy, Default)]
+pub struct OutputStatus {
+    pub streaming: bool,
+    pub recording: bool,
+    pub replay_buffer: bool,
+    // 仮想カメラに対応していないOBSではNone
+    pub virtual_cam: Option<bool>,
+}
+
+// OBSのパフォーマンス統計（GetStats）
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct ObsStats {
+    // %
+    pub cpu_usage: f64,
+    pub memory_usage_mb: f64,
+    pub active_fps: f64,
+    // レンダリングが間に合わずスキップしたフレーム
+    pub render_skipped_frames: u64,
+    pub render_total_frames: u64,
+    // エンコードが間に合わずスキップしたフレーム
+    pub output_skipped_frames: u64,
+    pub output_total_frames: u64,
+    // 録画先ドライブの空き容量
+    pub free_disk_space_mb: u64,
+}
+
+impl AppEvent for ObsStats {
+    const NAME: &'static str = "obs-stats";
+}
+
+// OBSがリプレイを書き出す場所と名前の設定
+#[derive(Serialize, Debug, Clone)]
+pub struct ReplayOutputInfo {
+    pub directory: PathBuf,
+    pub filename_formatting: String,
+    pub prefix: String,
+    pub suffix: String,
+    pub extension: String,
+}
+
+impl ReplayOutputInfo {
+    // OBSと同じ規則で prefix + 書式 + suffix を空白でつなぐ
+    pub fn filename_pattern(&self) -> String {
+        let mut name = String::new();
+        if !self.prefix.is_empty() {
+            name.push_str(&self.prefix);
+            if !self.prefix.ends_with(' ') {
+                name.push(' ');
+            }
+        }
+        name.push_str(&self.filename_formatting);
+        if !self.suffix.is_empty() {
+            if !self.suffix.starts_with(' ') {
+                name.push(' ');
+            }
+            name.push_str(&self.suffix);
+        }
+        format!("{}.{}", name, self.extension)
+    }
+}
+
+// 再生後に戻すための音声設定
+pub struct AudioState {
+    monitor_type: MonitorType,
+    muted: bool,
+}
 
 pub struct Obs {
     client: Option<Client>,
     host: OnceCell<String>,
     port: OnceCell<u16>,
     password: OnceCell<Option<String>>,
+    secure: OnceCell<bool>,
+    // Someなら実際のOBSには繋がずに偽物で応答する
+    mock: Option<Arc<MockObs>>,
+    // 1リクエストあたりのタイムアウト(秒)
+    request_timeout_sec: Arc<RwLock<u64>>,
+    // trueならリクエストと応答をtraceレベルで出力する
+    trace: Arc<RwLock<bool>>,
+    // シーンのトランジション中ならtrue（イベントリスナーが更新する）
+    in_transition: Arc<RwLock<bool>>,
+    // 録画の開始時刻 unix time(ms)。録画していなければNone（イベントリスナーが更新する）
+    recording_started_at: Arc<RwLock<Option<u64>>>,
 }
 
 impl Obs {
@@ -22,18 +242,65 @@ impl Obs {
             host: OnceCell::new(),
             port: OnceCell::new(),
             password: OnceCell::new(),
+            secure: OnceCell::new(),
+            mock: None,
+            request_timeout_sec: Arc::new(RwLock::new(DEFAULT_REQUEST_TIMEOUT_SEC)),
+            trace: Arc::new(RwLock::new(false)),
+            in_transition: Arc::new(RwLock::new(false)),
+            recording_started_at: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    pub fn new_mock(mock: Arc<MockObs>) -> Self {
+        Obs {
+            mock: Some(mock),
+            ..Self::new()
         }
     }
+
+    // secure=trueならwss://で接続する（証明書はシステムのルート証明書で検証）
+    async fn connect_client(
+        host: &str,
+        port: u16,
+        password: Option<&str>,
+        secure: bool,
+    ) -> Result<Client, obws::error::Error> {
+        let config = ConnectConfig {
+            host,
+            port,
+            password,
+            event_subscriptions: None,
+            broadcast_capacity: 100,
+            connect_timeout: std::time::Duration::from_secs(30),
+            dangerous: None,
+            tls: secure,
+        };
+        Client::connect_with_config(config).await
+    }
+
     pub async fn connect(
         &mut self,
         host: &str,
         port: u16,
         password: Option<&str>,
+        secure: bool,
     ) -> Result<(), obws::error::Error> {
-        let client = Client::connect(host, port, password).await?;
-        self.client = Some(client);
+        if *self.trace.read().unwrap() {
+            trace!(
+                "OBS connect -> {}:{} (secure: {}, password: {})",
+                host,
+                port,
+                secure,
+                if password.is_some() { "***" } else { "none" }
+            );
+        }
+        if self.mock.is_none() {
+            let client = Self::connect_client(host, port, password, secure).await?;
+            self.client = Some(client);
+        }
         self.host.set(host.to_string()).unwrap();
         self.port.set(port).unwrap();
+        self.secure.set(secure).unwrap();
         if let Some(pass) = password {
             self.password.set(Some(pass.to_string())).unwrap();
         } else {
@@ -42,6 +309,81 @@ impl Obs {
         Ok(())
     }
 
+    // 設定と共有して、接続中でも変更が反映されるようにする
+    pub fn set_request_timeout(&mut self, timeout_sec: Arc<RwLock<u64>>) {
+        self.request_timeout_sec = timeout_sec;
+    }
+
+    pub fn set_trace(&mut self, trace: Arc<RwLock<bool>>) {
+        self.trace = trace;
+    }
+
+    // 応答のないリクエストで呼び出し元が止まらないようにタイムアウトを付ける
+    // トレース時にどのリクエストか分かるよう呼び出し位置を記録する
+    #[track_caller]
+    fn request<T: std::fmt::Debug>(
+        &self,
+        fut: impl Future<Output = Result<T, obws::error::Error>>,
+    ) -> impl Future<Output = Result<T, RequestError>> {
+        self.request_with(None, fut)
+    }
+
+    // 引数のあるリクエストは送る中身（traced_bodyで作る）もトレースに残す
+    #[track_caller]
+    fn request_with<T: std::fmt::Debug>(
+        &self,
+        body: Option<String>,
+        fut: impl Future<Output = Result<T, obws::error::Error>>,
+    ) -> impl Future<Output = Result<T, RequestError>> {
+        let caller = std::panic::Location::caller();
+        let trace = *self.trace.read().unwrap();
+        let timeout = std::time::Duration::from_secs(*self.request_timeout_sec.read().unwrap());
+        async move {
+            if trace {
+                match &body {
+                    Some(body) => trace!("OBS request -> {}: {}", caller, body),
+                    None => trace!("OBS request -> {}", caller),
+                }
+            }
+            let res = match tokio::time::timeout(timeout, fut).await {
+                Ok(res) => res.map_err(RequestError::Obs),
+                Err(_) => Err(RequestError::Timeout(timeout)),
+            };
+            if trace {
+                match &res {
+                    Ok(response) => trace!(
+                        "OBS response <- {}: {}",
+                        caller,
+                        redact_secrets(&format!("{response:?}"))
+                    ),
+                    Err(e) => trace!(
+                        "OBS response <- {}: error: {}",
+                        caller,
+                        redact_secrets(&e.to_string())
+                    ),
+                }
+            }
+            res
+        }
+    }
+
+    // トレース時だけ、リクエストの中身を秘密情報を伏せた文字列にする
+    fn traced_body(&self, request: &impl Serialize) -> Option<String> {
+        if !*self.trace.read().unwrap() {
+            return None;
+        }
+        serde_json::to_string(request)
+            .ok()
+            .map(|body| redact_secrets(&body))
+    }
+
+    // websocketを閉じる（OBSが応答しないと返ってこないことがある）
+    pub async fn disconnect(mut self) {
+        if let Some(mut client) = self.client.take() {
+            client.disconnect().await;
+        }
+    }
+
     fn get_client(&self) -> Result<&Client, String> {
         let client = &self.client;
         let client = match client {
@@ -52,37 +394,385 @@ impl Obs {
     }
 
     async fn get_replay_buffer_status(&self, client: &Client) -> Result<bool, String> {
-        let res = client.replay_buffer().status().await;
+        let res = self.request(client.replay_buffer().status()).await;
         match res {
             Ok(res) => Ok(res),
-            Err(_) => Err("failed to get replay_buffer status".to_string()),
+            Err(e) => Err(format!("failed to get replay_buffer status: {e}")),
         }
     }
     pub async fn set_replay_buffer(&self) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
         let client = self.get_client()?;
         let status = self.get_replay_buffer_status(client).await?;
         // もうONだったらreturn
         if status {
             return Ok(());
         }
-        let res = client.replay_buffer().start().await;
+        let res = self.request(client.replay_buffer().start()).await;
         if let Err(e) = res {
             return Err(e.to_string());
         }
         Ok(())
     }
 
-    pub async fn save_replay_buffer(&self) -> Result<(), String> {
+    // リプレイバッファの最大時間(秒)
+    pub async fn get_replay_buffer_length(&self) -> Result<u64, String> {
+        if self.mock.is_some() {
+            return Ok(mock_obs::MOCK_BUFFER_LENGTH_SEC);
+        }
+        let category = self.output_category().await?;
+        let value = self.get_profile_parameter(category, "RecRBTime").await?;
+        value
+            .parse()
+            .map_err(|e| format!("Invalid replay buffer length {:?}: {}", value, e))
+    }
+
+    // 動作中のリプレイバッファには反映されない（次に開始したときから効く）
+    pub async fn set_replay_buffer_length(&self, length_sec: u64) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        let category = self.output_category().await?;
         let client = self.get_client()?;
-        let res = client.replay_buffer().save().await;
+        let value = length_sec.to_string();
+        let parameter = obws::requests::config::SetProfileParameter {
+            category,
+            name: "RecRBTime",
+            value: Some(&value),
+        };
+        self.request_with(
+            self.traced_body(&parameter),
+            client.config().set_profile_parameter(parameter),
+        )
+        .await
+        .map_err(|e| format!("Failed to set replay buffer length: {e}"))
+    }
+
+    pub async fn is_replay_buffer_active(&self) -> Result<bool, String> {
+        if self.mock.is_some() {
+            return Ok(false);
+        }
+        let client = self.get_client()?;
+        self.get_replay_buffer_status(client).await
+    }
+
+    // 設定を反映させるために止めて開始し直す（溜まっていた分は消える）
+    pub async fn restart_replay_buffer(&self) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        let client = self.get_client()?;
+        self.request(client.replay_buffer().stop())
+            .await
+            .map_err(|e| format!("Failed to stop replay buffer: {e}"))?;
+        // 止まりきるまで開始できない
+        for _ in 0..50 {
+            if !self.get_replay_buffer_status(client).await? {
+                return self
+                    .request(client.replay_buffer().start())
+                    .await
+                    .map_err(|e| format!("Failed to start replay buffer: {e}"));
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        Err("Replay buffer did not stop".to_string())
+    }
+
+    // 配信か録画のどちらかが動いているか
+    pub async fn is_output_active(&self) -> Result<bool, String> {
+        if self.mock.is_some() {
+            return Ok(true);
+        }
+        let client = self.get_client()?;
+        let streaming = self
+            .request(client.streaming().status())
+            .await
+            .map_err(|e| format!("Failed to get streaming status: {e}"))?;
+        if streaming.active {
+            return Ok(true);
+        }
+        let recording = self
+            .request(client.recording().status())
+            .await
+            .map_err(|e| format!("Failed to get recording status: {e}"))?;
+        Ok(recording.active)
+    }
+
+    pub async fn get_output_status(&self) -> Result<OutputStatus, String> {
+        if self.mock.is_some() {
+            return Ok(OutputStatus {
+                replay_buffer: true,
+                ..Default::default()
+            });
+        }
+        let client = self.get_client()?;
+        let streaming = self
+            .request(client.streaming().status())
+            .await
+            .map_err(|e| format!("Failed to get streaming status: {e}"))?;
+        let recording = self
+            .request(client.recording().status())
+            .await
+            .map_err(|e| format!("Failed to get recording status: {e}"))?;
+        let virtual_cam = match self.get_virtual_cam_status(client).await {
+            Ok(active) => Some(active),
+            Err(e) => {
+                debug!("{}", e);
+                None
+            }
+        };
+        Ok(OutputStatus {
+            streaming: streaming.active,
+            recording: recording.active,
+            replay_buffer: self.get_replay_buffer_status(client).await?,
+            virtual_cam,
+        })
+    }
+
+    // 仮想カメラがないOBS（Linuxでv4l2loopbackがない等）ではエラーになる
+    async fn get_virtual_cam_status(&self, client: &Client) -> Result<bool, String> {
+        self.request(client.virtual_cam().status())
+            .await
+            .map_err(|e| format!("Virtual camera is not available in this OBS: {e}"))
+    }
+
+    pub async fn start_virtual_cam(&self) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        let client = self.get_client()?;
+        if self.get_virtual_cam_status(client).await? {
+            return Ok(());
+        }
+        self.request(client.virtual_cam().start())
+            .await
+            .map_err(|e| format!("Failed to start virtual camera: {e}"))
+    }
+
+    pub async fn stop_virtual_cam(&self) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        let client = self.get_client()?;
+        if !self.get_virtual_cam_status(client).await? {
+            return Ok(());
+        }
+        self.request(client.virtual_cam().stop())
+            .await
+            .map_err(|e| format!("Failed to stop virtual camera: {e}"))
+    }
+
+    // 連続録画モード用。録画中でなければ録画を開始する
+    pub async fn set_recording(&self) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        if self.update_recording_started_at().await? {
+            return Ok(());
+        }
+        let client = self.get_client()?;
+        let res = self.request(client.recording().start()).await;
         if let Err(e) = res {
             return Err(e.to_string());
         }
+        // 開始のイベントが届けばその時刻で上書きされる
+        *self.recording_started_at.write().unwrap() = Some(crate::unix_millis());
         Ok(())
     }
 
-    pub async fn init_vlc_source(&self) -> Result<(), String> {
-        if self.is_exit_vlc_soruce().await? {
+    // 録画中なら経過時間から開始時刻を求めて覚える。録画中ならtrue
+    async fn update_recording_started_at(&self) -> Result<bool, String> {
+        let client = self.get_client()?;
+        let status = self
+            .request(client.recording().status())
+            .await
+            .map_err(|e| format!("failed to get recording status: {e}"))?;
+        let started_at = status.active.then(|| {
+            let elapsed = status.duration.whole_milliseconds().max(0) as u64;
+            crate::unix_millis().saturating_sub(elapsed)
+        });
+        *self.recording_started_at.write().unwrap() = started_at;
+        Ok(status.active)
+    }
+
+    // 連続録画のマーカーの基準にする
+    pub fn recording_started_at(&self) -> Option<u64> {
+        *self.recording_started_at.read().unwrap()
+    }
+
+    // タイムアウトでもOBSは保存しているかもしれないので、呼び出し側で区別できるようにする
+    pub async fn save_replay_buffer(&self) -> Result<(), RequestError> {
+        if let Some(mock) = &self.mock {
+            mock.save_replay();
+            return Ok(());
+        }
+        let client = self.get_client().map_err(|_| RequestError::NotConnected)?;
+        self.request(client.replay_buffer().save()).await
+    }
+
+    pub async fn get_last_replay(&self) -> Option<PathBuf> {
+        if let Some(mock) = &self.mock {
+            return mock.last_replay();
+        }
+        let client = self.get_client().ok()?;
+        self.request(client.replay_buffer().last_replay())
+            .await
+            .ok()
+            .map(PathBuf::from)
+    }
+
+    // last_replayがbeforeから変わるまで待って、新しいパスを返す
+    pub async fn wait_for_new_replay(&self, before: Option<PathBuf>) -> Result<PathBuf, String> {
+        // 書き出しが終わるまでlast_replayは更新されないのでポーリング
+        for _ in 0..50 {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            if let Some(path) = self
+                .get_last_replay()
+                .await
+                .filter(|p| Some(p) != before.as_ref())
+            {
+                return Ok(path);
+            }
+        }
+        Err("Timed out waiting for replay to be saved".to_string())
+    }
+
+    // 録画・リプレイのファイル名の書式 (例: "%CCYY-%MM-%DD %hh-%mm-%ss")
+    pub async fn get_filename_formatting(&self) -> Result<String, String> {
+        if self.mock.is_some() {
+            return Ok(String::new());
+        }
+        self.get_profile_parameter("Output", "FilenameFormatting")
+            .await
+            .map_err(|e| format!("Failed to get filename formatting: {e}"))
+    }
+
+    // 未設定なら既定値、それもなければ空文字
+    async fn get_profile_parameter(&self, category: &str, name: &str) -> Result<String, String> {
+        let client = self.get_client()?;
+        let parameter = self
+            .request(client.config().profile_parameter(category, name))
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(parameter
+            .value
+            .or(parameter.default_value)
+            .unwrap_or_default())
+    }
+
+    pub async fn get_replay_output_info(&self) -> Result<ReplayOutputInfo, String> {
+        if self.mock.is_some() {
+            return Ok(ReplayOutputInfo {
+                directory: PathBuf::from(mock_obs::MOCK_REPLAY_DIR),
+                filename_formatting: "Replay_<unix time(ms)>".to_string(),
+                prefix: String::new(),
+                suffix: String::new(),
+                extension: "mkv".to_string(),
+            });
+        }
+        let directory = self.get_record_directory().await?;
+        let category = self.output_category().await?;
+        let format = self.get_profile_parameter(category, "RecFormat2").await?;
+        // fragmented_mp4 -> mp4 のように拡張子に直す
+        let extension = match format.as_str() {
+            "" => "mkv".to_string(),
+            "mpegts" => "ts".to_string(),
+            format => format
+                .trim_start_matches("fragmented_")
+                .trim_start_matches("hybrid_")
+                .to_string(),
+        };
+        Ok(ReplayOutputInfo {
+            directory: PathBuf::from(directory),
+            filename_formatting: self.get_filename_formatting().await?,
+            prefix: self.get_profile_parameter(category, "RecRBPrefix").await?,
+            suffix: self.get_profile_parameter(category, "RecRBSuffix").await?,
+            extension,
+        })
+    }
+
+    pub async fn get_record_directory(&self) -> Result<String, String> {
+        if self.mock.is_some() {
+            return Ok(mock_obs::MOCK_REPLAY_DIR.to_string());
+        }
+        let client = self.get_client()?;
+        self.request(client.config().record_directory())
+            .await
+            .map_err(|e| format!("Failed to get record directory: {e}"))
+    }
+
+    // 出力モードによって設定の置き場所が違う
+    async fn output_category(&self) -> Result<&'static str, String> {
+        let mode = self.get_profile_parameter("Output", "Mode").await?;
+        Ok(if mode == "Advanced" {
+            "AdvOut"
+        } else {
+            "SimpleOutput"
+        })
+    }
+
+    // OBSの設定値のまま返す（mkv, fragmented_mp4など）
+    pub async fn get_replay_output_format(&self) -> Result<String, String> {
+        if self.mock.is_some() {
+            return Ok("mkv".to_string());
+        }
+        let category = self.output_category().await?;
+        self.get_profile_parameter(category, "RecFormat2").await
+    }
+
+    // 動作中のリプレイバッファには次に開始したときから反映される
+    pub async fn set_replay_output_format(&self, format: &str) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        let category = self.output_category().await?;
+        let client = self.get_client()?;
+        let parameter = obws::requests::config::SetProfileParameter {
+            category,
+            name: "RecFormat2",
+            value: Some(format),
+        };
+        self.request_with(
+            self.traced_body(&parameter),
+            client.config().set_profile_parameter(parameter),
+        )
+        .await
+        .map_err(|e| format!("Failed to set replay output format: {e}"))
+    }
+
+    pub async fn set_filename_formatting(&self, format: &str) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        let client = self.get_client()?;
+        let parameter = obws::requests::config::SetProfileParameter {
+            category: "Output",
+            name: "FilenameFormatting",
+            value: Some(format),
+        };
+        self.request_with(
+            self.traced_body(&parameter),
+            client.config().set_profile_parameter(parameter),
+        )
+        .await
+        .map_err(|e| format!("Failed to set filename formatting: {e}"))
+    }
+
+    // 保存して、OBSが書き出したファイルのパスを返す
+    pub async fn save_replay_buffer_with_path(&self) -> Result<PathBuf, String> {
+        let before = self.get_last_replay().await;
+        self.save_replay_buffer().await.map_err(|e| e.to_string())?;
+        self.wait_for_new_replay(before).await
+    }
+
+    // OBSのVLCソースには先読みの設定がないので、キャッシュ時間だけ指定できる
+    pub async fn init_vlc_source(&self, source: &str, caching_ms: u64) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        if self.is_exit_vlc_soruce(source).await? {
             return Ok(());
         }
 
@@ -95,29 +785,154 @@ impl Obs {
             playback_behavior:
                 obws::requests::custom::source_settings::PlaybackBehavior::StopRestart,
             playlist: &[],
-            network_caching: Duration::milliseconds(100),
+            network_caching: Duration::milliseconds(caching_ms as i64),
             track: 1,
             subtitle_enable: false,
             subtitle: 0,
         };
         let create = obws::requests::inputs::Create {
             scene: current_scene.id.into(),
-            input: UNIQUE_REPLAY_SOURCE_NAME,
+            input: source,
             kind: obws::requests::custom::source_settings::SOURCE_VLC_SOURCE,
             settings: Some(vlc_setting),
             enabled: Some(false),
         };
-        let res = client.inputs().create(create).await;
+        let res = self
+            .request_with(self.traced_body(&create), client.inputs().create(create))
+            .await;
         match res {
-            Ok(_) => debug!("VLC source created"),
+            Ok(_) => debug!("VLC source created: {}", source),
             Err(e) => return Err(format!("Failed to create VLC source: {e}")),
         }
         Ok(())
     }
 
-    pub async fn play_vlc_source(&self, movie_pathes: &[PathBuf]) -> Result<(), String> {
+    // ユーザーがOBS側でVLCソースを消していたら作り直す。作り直したらtrue
+    pub async fn ensure_vlc_source(&self, source: &str, caching_ms: u64) -> Result<bool, String> {
+        if self.mock.is_some() || self.is_exit_vlc_soruce(source).await? {
+            return Ok(false);
+        }
+        warn!("VLC source {} was not found, creating it", source);
+        self.init_vlc_source(source, caching_ms).await?;
+        Ok(true)
+    }
+
+    // highlights_sceneが指定されていればそのシーンに切り替えてから、各VLCソースで再生する
+    pub async fn play_vlc_sources(
+        &self,
+        playlists: &[VlcPlaylist<'_>],
+        options: &PlaybackOptions,
+    ) -> Result<PlaybackStarted, String> {
+        if let Some(mock) = &self.mock {
+            let longest = playlists.iter().map(|p| p.clips.len()).max();
+            mock.play(longest.unwrap_or_default());
+            return Ok(PlaybackStarted::default());
+        }
+        let Some(scene) = options.highlights_scene.as_deref() else {
+            let recreated_source = self.start_vlc_playlists(playlists, options).await?;
+            return Ok(PlaybackStarted {
+                original_scene: None,
+                recreated_source,
+            });
+        };
+        let transition = options.transition.as_ref();
+        let original = self.get_current_scene().await?.id.name;
+        self.set_current_scene_with_transition(scene, transition)
+            .await?;
+        match self.start_vlc_playlists(playlists, options).await {
+            Ok(recreated_source) => Ok(PlaybackStarted {
+                original_scene: Some(original),
+                recreated_source,
+            }),
+            Err(e) => {
+                // 失敗したら元のシーンに戻す
+                if let Err(e) = self
+                    .set_current_scene_with_transition(&original, transition)
+                    .await
+                {
+                    warn!("{}", e);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    // 読み込めても再生が始まらないことがあるので、再生中になったかまで確認する
+    async fn start_vlc_playlists(
+        &self,
+        playlists: &[VlcPlaylist<'_>],
+        options: &PlaybackOptions,
+    ) -> Result<bool, String> {
+        let recreated_source = self.load_vlc_playlists(playlists, options).await?;
+        for playlist in playlists {
+            self.wait_vlc_playing(playlist.source).await?;
+        }
+        Ok(recreated_source)
+    }
+
+    // どれかのVLCソースを作り直したらtrue
+    async fn load_vlc_playlists(
+        &self,
+        playlists: &[VlcPlaylist<'_>],
+        options: &PlaybackOptions,
+    ) -> Result<bool, String> {
+        let mut recreated_source = false;
+        for playlist in playlists {
+            recreated_source |= self.load_vlc_playlist(playlist, options).await?;
+        }
+        Ok(recreated_source)
+    }
+
+    // VLCソースにプレイリストを設定して、現在のシーンで有効化する
+    // VLCソースを作り直したらtrue
+    async fn load_vlc_playlist(
+        &self,
+        playlist: &VlcPlaylist<'_>,
+        options: &PlaybackOptions,
+    ) -> Result<bool, String> {
+        let source = playlist.source;
+        // 切り替え後のシーンに作り直すため、ここで確認する
+        let recreated_source = self.ensure_vlc_source(source, options.caching_ms).await?;
+        self.set_vlc_playlist_settings(source, playlist.clips, options)
+            .await?;
+        // Sourceの有効化
+        let (current_scene_id, item_id) = self.find_scene_item(source).await?;
+        match playlist.transform {
+            Some(transform) => {
+                self.transform_scene_item(current_scene_id.clone(), item_id, transform)
+                    .await?
+            }
+            None => {
+                self.fit_scene_item(current_scene_id.clone(), item_id, options.fit)
+                    .await?
+            }
+        }
+        self.set_vlc_scene_item_enabled(current_scene_id, item_id, true)
+            .await?;
+        Ok(recreated_source)
+    }
+
+    // 再生中のVLCソースのプレイリストだけを差し替える（表示や位置はそのまま）
+    pub async fn update_vlc_playlist(
+        &self,
+        source: &str,
+        clips: &[PathBuf],
+        options: &PlaybackOptions,
+    ) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        self.set_vlc_playlist_settings(source, clips, options).await
+    }
+
+    async fn set_vlc_playlist_settings(
+        &self,
+        source: &str,
+        clips: &[PathBuf],
+        options: &PlaybackOptions,
+    ) -> Result<(), String> {
         let client = self.get_client()?;
-        let playlists: Vec<SlideshowFile> = movie_pathes
+        let playlists: Vec<SlideshowFile> = clips
             .iter()
             .map(|path| SlideshowFile {
                 value: path.as_path(),
@@ -126,103 +941,784 @@ impl Obs {
             })
             .collect();
         let vlc_setting = obws::requests::custom::source_settings::VlcSource {
-            loop_: false,
+            loop_: options.loop_playlist,
             shuffle: false,
             playback_behavior:
                 obws::requests::custom::source_settings::PlaybackBehavior::StopRestart,
             playlist: &playlists,
-            network_caching: Duration::milliseconds(100),
+            network_caching: Duration::milliseconds(options.caching_ms as i64),
             track: 1,
             subtitle_enable: false,
             subtitle: 0,
         };
         let input_setting = obws::requests::inputs::SetSettings {
-            input: obws::requests::inputs::InputId::Name(UNIQUE_REPLAY_SOURCE_NAME),
+            input: obws::requests::inputs::InputId::Name(source),
             overlay: Some(true),
             settings: &vlc_setting,
         };
-        let res = client.inputs().set_settings(input_setting).await;
+        let res = self
+            .request(client.inputs().set_settings(input_setting))
+            .await;
         match res {
             Ok(_) => debug!("VLC source updated"),
             Err(e) => return Err(format!("Failed to create VLC source: {e}")),
         }
-        // Sourceの有効化
+        Ok(())
+    }
+
+    // 現在のシーンにあるソースのシーンアイテム
+    async fn find_scene_item(
+        &self,
+        source: &str,
+    ) -> Result<(obws::responses::scenes::SceneId, i64), String> {
+        let client = self.get_client()?;
         let current_scene = self.get_current_scene().await?;
         let current_scene_id = current_scene.id;
-        let scene_items = client
-            .scene_items()
-            .list(current_scene_id.clone().into())
+        let scene_items = self
+            .request(client.scene_items().list(current_scene_id.clone().into()))
             .await;
         let scene_items = match scene_items {
             Ok(scene_items) => scene_items,
             Err(_) => return Err("Failed to get scene items".to_string()),
         };
-        let unique_replay_source_item = match scene_items
-            .iter()
-            .find(|&item| item.source_name == UNIQUE_REPLAY_SOURCE_NAME)
-        {
+        let scene_item = match scene_items.iter().find(|&item| item.source_name == source) {
             Some(d) => d,
-            None => return Err("Failed to find unique_replay_source_item".to_string()),
+            None => return Err(format!("Failed to find scene item: {source}")),
         };
+        Ok((current_scene_id, scene_item.id))
+    }
+
+    pub async fn get_canvas_size(&self) -> Result<(f32, f32), String> {
+        if self.mock.is_some() {
+            return Ok(mock_obs::MOCK_CANVAS_SIZE);
+        }
+        let client = self.get_client()?;
+        let video = self
+            .request(client.config().video_settings())
+            .await
+            .map_err(|e| format!("Failed to get video settings: {e}"))?;
+        Ok((video.base_width as f32, video.base_height as f32))
+    }
+
+    // 現在のシーンにあるソースの位置とサイズを変える
+    pub async fn set_source_transform(
+        &self,
+        source: &str,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    ) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        let (scene, item_id) = self.find_scene_item(source).await?;
+        let transform = SourceTransform {
+            x,
+            y,
+            width,
+            height,
+        };
+        self.transform_scene_item(scene, item_id, transform).await
+    }
+
+    async fn transform_scene_item(
+        &self,
+        scene: obws::responses::scenes::SceneId,
+        item_id: i64,
+        transform: SourceTransform,
+    ) -> Result<(), String> {
+        let (canvas_width, canvas_height) = self.get_canvas_size().await?;
+        transform.validate(canvas_width, canvas_height)?;
+        let client = self.get_client()?;
+        let transform = SceneItemTransform {
+            position: Some(Position {
+                x: Some(transform.x),
+                y: Some(transform.y),
+            }),
+            alignment: Some(Alignment::TOP | Alignment::LEFT),
+            bounds: Some(Bounds {
+                r#type: Some(BoundsType::ScaleInner),
+                alignment: Some(Alignment::CENTER),
+                width: Some(transform.width),
+                height: Some(transform.height),
+            }),
+            ..Default::default()
+        };
+        let set_transform = obws::requests::scene_items::SetTransform {
+            scene: scene.into(),
+            item_id,
+            transform,
+        };
+        self.request_with(
+            self.traced_body(&set_transform),
+            client.scene_items().set_transform(set_transform),
+        )
+        .await
+        .map_err(|e| format!("Failed to set source transform: {e}"))
+    }
+
+    async fn set_vlc_scene_item_enabled(
+        &self,
+        scene: obws::responses::scenes::SceneId,
+        item_id: i64,
+        enabled: bool,
+    ) -> Result<(), String> {
+        let client = self.get_client()?;
         let set_enabled: obws::requests::scene_items::SetEnabled<'_> =
             obws::requests::scene_items::SetEnabled {
-                scene: current_scene_id.into(),
-                item_id: unique_replay_source_item.id,
-                enabled: true,
+                scene: scene.into(),
+                item_id,
+                enabled,
             };
-        let res = client.scene_items().set_enabled(set_enabled).await;
+        let res = self
+            .request(client.scene_items().set_enabled(set_enabled))
+            .await;
         if let Err(e) = res {
             return Err(format!("Failed to set VLC source enabled: {e}"));
         }
         Ok(())
     }
 
+    // 再生していない間に黒い画面が映らないよう、現在のシーンのVLCソースを隠す
+    pub async fn hide_vlc_source(&self, source: &str) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        let (scene, item_id) = self.find_scene_item(source).await?;
+        self.set_vlc_scene_item_enabled(scene, item_id, false).await
+    }
+
+    // シーンアイテムをキャンバス中央に合わせる
+    async fn fit_scene_item(
+        &self,
+        scene: obws::responses::scenes::SceneId,
+        item_id: i64,
+        fit: HighlightFit,
+    ) -> Result<(), String> {
+        let bounds_type = match fit {
+            HighlightFit::None => return Ok(()),
+            HighlightFit::Fit => BoundsType::ScaleInner,
+            HighlightFit::Fill => BoundsType::ScaleOuter,
+            HighlightFit::Stretch => BoundsType::Stretch,
+        };
+        let (width, height) = self.get_canvas_size().await?;
+        let client = self.get_client()?;
+        let transform = SceneItemTransform {
+            position: Some(Position {
+                x: Some(width / 2.0),
+                y: Some(height / 2.0),
+            }),
+            alignment: Some(Alignment::CENTER),
+            bounds: Some(Bounds {
+                r#type: Some(bounds_type),
+                alignment: Some(Alignment::CENTER),
+                width: Some(width),
+                height: Some(height),
+            }),
+            ..Default::default()
+        };
+        let set_transform = obws::requests::scene_items::SetTransform {
+            scene: scene.into(),
+            item_id,
+            transform,
+        };
+        self.request_with(
+            self.traced_body(&set_transform),
+            client.scene_items().set_transform(set_transform),
+        )
+        .await
+        .map_err(|e| format!("Failed to set VLC source transform: {e}"))
+    }
+
+    pub async fn stop_vlc_source(&self, source: &str) -> Result<(), String> {
+        if let Some(mock) = &self.mock {
+            mock.stop();
+            return Ok(());
+        }
+        let client = self.get_client()?;
+        self.request(
+            client
+                .media_inputs()
+                .trigger_action(InputId::Name(source), MediaAction::Stop),
+        )
+        .await
+        .map_err(|e| format!("Failed to stop VLC source: {e}"))
+    }
+
+    pub async fn pause_vlc_source(&self, source: &str) -> Result<(), String> {
+        self.trigger_vlc_action(source, MediaAction::Pause).await
+    }
+
+    pub async fn resume_vlc_source(&self, source: &str) -> Result<(), String> {
+        self.trigger_vlc_action(source, MediaAction::Play).await
+    }
+
+    async fn trigger_vlc_action(&self, source: &str, action: MediaAction) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        let client = self.get_client()?;
+        self.request(
+            client
+                .media_inputs()
+                .trigger_action(InputId::Name(source), action),
+        )
+        .await
+        .map_err(|e| format!("Failed to control VLC source: {e}"))
+    }
+
+    pub async fn is_vlc_playing(&self, source: &str) -> Result<bool, String> {
+        if self.mock.is_some() {
+            return Ok(true);
+        }
+        let client = self.get_client()?;
+        let status = self
+            .request(client.media_inputs().status(InputId::Name(source)))
+            .await
+            .map_err(|e| format!("Failed to get media status: {e}"))?;
+        Ok(status.state == MediaState::Playing)
+    }
+
+    pub async fn wait_vlc_playing(&self, source: &str) -> Result<(), String> {
+        for _ in 0..PLAYING_POLL_ATTEMPTS {
+            if self.is_vlc_playing(source).await? {
+                return Ok(());
+            }
+            tokio::time::sleep(PLAYING_POLL_INTERVAL).await;
+        }
+        Err(format!("{} did not start playing", source))
+    }
+
+    pub fn is_in_transition(&self) -> bool {
+        *self.in_transition.read().unwrap()
+    }
+
+    // トランジションが終わったらtrue、max_wait待っても終わらなければfalse
+    pub async fn wait_transition_end(&self, max_wait: std::time::Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + max_wait;
+        while self.is_in_transition() {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(TRANSITION_POLL_INTERVAL).await;
+        }
+        true
+    }
+
+    // 再生中・一時停止中・読み込み中ならtrue（最後まで再生し終えていればfalse）
+    pub async fn is_vlc_source_active(&self, source: &str) -> Result<bool, String> {
+        if self.mock.is_some() {
+            return Ok(true);
+        }
+        let client = self.get_client()?;
+        let status = self
+            .request(client.media_inputs().status(InputId::Name(source)))
+            .await
+            .map_err(|e| format!("Failed to get media status: {e}"))?;
+        Ok(matches!(
+            status.state,
+            MediaState::Playing | MediaState::Paused | MediaState::Opening | MediaState::Buffering
+        ))
+    }
+
+    // OBSの録画先ドライブの空き容量(MB)
+    pub async fn get_available_disk_space_mb(&self) -> Result<u64, String> {
+        Ok(self.get_stats().await?.free_disk_space_mb)
+    }
+
+    pub async fn get_stats(&self) -> Result<ObsStats, String> {
+        if self.mock.is_some() {
+            return Ok(ObsStats {
+                free_disk_space_mb: mock_obs::MOCK_FREE_SPACE_MB,
+                ..Default::default()
+            });
+        }
+        let client = self.get_client()?;
+        let stats = self
+            .request(client.general().stats())
+            .await
+            .map_err(|e| format!("Failed to get OBS stats: {e}"))?;
+        Ok(ObsStats {
+            cpu_usage: stats.cpu_usage,
+            memory_usage_mb: stats.memory_usage,
+            active_fps: stats.active_fps,
+            render_skipped_frames: stats.render_skipped_frames as u64,
+            render_total_frames: stats.render_total_frames as u64,
+            output_skipped_frames: stats.output_skipped_frames as u64,
+            output_total_frames: stats.output_total_frames as u64,
+            free_disk_space_mb: stats.available_disk_space as u64,
+        })
+    }
+
+    pub async fn set_current_scene(&self, scene: &str) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        let client = self.get_client()?;
+        if self.is_studio_mode_enabled().await? {
+            return self.set_current_scene_in_studio_mode(scene).await;
+        }
+        self.request(client.scenes().set_current_program_scene(scene))
+            .await
+            .map_err(|e| format!("Failed to switch scene to {scene}: {e}"))
+    }
+
+    // プログラムに出ているシーンをPNGで撮ってpathに書き出す
+    // OBSが別のPCで動いていてもこちらに保存できるように、画像データを受け取って書く
+    pub async fn save_program_screenshot(&self, path: &Path) -> Result<(), String> {
+        if self.mock.is_some() {
+            debug!("Mock OBS took screenshot: {:?}", path);
+            return Ok(());
+        }
+        let client = self.get_client()?;
+        let scene = self
+            .request(client.scenes().current_program_scene())
+            .await
+            .map_err(|e| format!("Failed to get program scene: {e}"))?
+            .id
+            .name;
+        let image = self
+            .request(client.sources().take_screenshot(TakeScreenshot {
+                source: SourceId::Name(&scene),
+                width: None,
+                height: None,
+                compression_quality: None,
+                format: "png",
+            }))
+            .await
+            .map_err(|e| format!("Failed to take screenshot of {scene}: {e}"))?;
+        // data:image/png;base64,... の形で返ってくる
+        let data = image
+            .split_once(',')
+            .map_or(image.as_str(), |(_, data)| data);
+        let bytes = STANDARD
+            .decode(data)
+            .map_err(|e| format!("Failed to decode screenshot: {e}"))?;
+        std::fs::write(path, bytes).map_err(|e| format!("Failed to write screenshot: {e}"))
+    }
+
+    async fn is_studio_mode_enabled(&self) -> Result<bool, String> {
+        let client = self.get_client()?;
+        self.request(client.ui().studio_mode_enabled())
+            .await
+            .map_err(|e| format!("Failed to get studio mode: {e}"))
+    }
+
+    // スタジオモードではプレビューに送ってからトランジションでプログラムに出す
+    // ユーザーが準備していたプレビューは元に戻す
+    async fn set_current_scene_in_studio_mode(&self, scene: &str) -> Result<(), String> {
+        let client = self.get_client()?;
+        let preview = self
+            .request(client.scenes().current_preview_scene())
+            .await
+            .map_err(|e| format!("Failed to get preview scene: {e}"))?
+            .id
+            .name;
+        self.request(client.scenes().set_current_preview_scene(scene))
+            .await
+            .map_err(|e| format!("Failed to set preview scene to {scene}: {e}"))?;
+        self.request(client.transitions().trigger())
+            .await
+            .map_err(|e| format!("Failed to switch scene to {scene}: {e}"))?;
+        if preview != scene
+            && let Err(e) = self
+                .request(client.scenes().set_current_preview_scene(preview.as_str()))
+                .await
+        {
+            warn!("Failed to restore preview scene {}: {e}", preview);
+        }
+        Ok(())
+    }
+
+    // transitionがNoneならOBSで選択中のトランジションのまま切り替える
+    pub async fn set_current_scene_with_transition(
+        &self,
+        scene: &str,
+        transition: Option<&SceneTransition>,
+    ) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        if let Some(transition) = transition {
+            let client = self.get_client()?;
+            self.request(client.transitions().set_current(&transition.name))
+                .await
+                .map_err(|e| format!("Failed to set transition {}: {e}", transition.name))?;
+            // Cutなど長さ固定のトランジションは設定できないので無視する
+            let duration = Duration::milliseconds(transition.duration_ms as i64);
+            if let Err(e) = self
+                .request(client.transitions().set_current_duration(duration))
+                .await
+            {
+                warn!("Failed to set transition duration: {e}");
+            }
+        }
+        self.set_current_scene(scene).await
+    }
+
+    // 存在しないプロファイルならエラー（切り替え済みなら何もしない）
+    pub async fn set_current_profile(&self, name: &str) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        let client = self.get_client()?;
+        let profiles = self
+            .request(client.profiles().list())
+            .await
+            .map_err(|e| format!("Failed to get profiles: {e}"))?;
+        if profiles.current == name {
+            return Ok(());
+        }
+        if !profiles.profiles.iter().any(|profile| profile == name) {
+            return Err(format!("Profile {name} does not exist in OBS"));
+        }
+        self.request(client.profiles().set_current(name))
+            .await
+            .map_err(|e| format!("Failed to switch profile to {name}: {e}"))
+    }
+
+    // 存在しないシーンコレクションならエラー（切り替え済みなら何もしない）
+    pub async fn set_current_scene_collection(&self, name: &str) -> Result<(), String> {
+        if self.mock.is_some() {
+            return Ok(());
+        }
+        let client = self.get_client()?;
+        let collections = self
+            .request(client.scene_collections().list())
+            .await
+            .map_err(|e| format!("Failed to get scene collections: {e}"))?;
+        if collections.current == name {
+            return Ok(());
+        }
+        if !collections
+            .collections
+            .iter()
+            .any(|collection| collection == name)
+        {
+            return Err(format!("Scene collection {name} does not exist in OBS"));
+        }
+        self.request(client.scene_collections().set_current(name))
+            .await
+            .map_err(|e| format!("Failed to switch scene collection to {name}: {e}"))
+    }
+
+    pub async fn scene_exists(&self, scene: &str) -> Result<bool, String> {
+        if self.mock.is_some() {
+            return Ok(true);
+        }
+        let client = self.get_client()?;
+        let scenes = self
+            .request(client.scenes().list())
+            .await
+            .map_err(|e| format!("Failed to get scenes: {e}"))?;
+        Ok(scenes.scenes.iter().any(|s| s.id.name == scene))
+    }
+
+    pub async fn get_source_audio_state(&self, source: &str) -> Result<AudioState, String> {
+        let client = self.get_client()?;
+        let monitor_type = self
+            .request(client.inputs().audio_monitor_type(InputId::Name(source)))
+            .await
+            .map_err(|e| format!("Failed to get audio monitor type: {e}"))?;
+        let muted = self
+            .request(client.inputs().muted(InputId::Name(source)))
+            .await
+            .map_err(|e| format!("Failed to get mute state: {e}"))?;
+        Ok(AudioState {
+            monitor_type,
+            muted,
+        })
+    }
+
+    pub async fn set_source_audio_monitor(
+        &self,
+        source: &str,
+        mode: MonitorType,
+    ) -> Result<(), String> {
+        let client = self.get_client()?;
+        self.request(
+            client
+                .inputs()
+                .set_audio_monitor_type(InputId::Name(source), mode),
+        )
+        .await
+        .map_err(|e| format!("Failed to set audio monitor type: {e}"))
+    }
+
+    pub async fn set_source_muted(&self, source: &str, muted: bool) -> Result<(), String> {
+        let client = self.get_client()?;
+        self.request(client.inputs().set_muted(InputId::Name(source), muted))
+            .await
+            .map_err(|e| format!("Failed to set mute state: {e}"))
+    }
+
+    // VLCソースに音声モードを適用して、元の設定を返す
+    // 音声に対応していないソースはログだけ出してスキップ
+    pub async fn apply_playback_audio_mode(&self, mode: PlaybackAudioMode) -> Option<AudioState> {
+        if mode == PlaybackAudioMode::Normal || self.mock.is_some() {
+            return None;
+        }
+        let prev = match self.get_source_audio_state(UNIQUE_REPLAY_SOURCE_NAME).await {
+            Ok(prev) => prev,
+            Err(e) => {
+                warn!("VLC source does not support audio control, skip: {}", e);
+                return None;
+            }
+        };
+        let res = match mode {
+            PlaybackAudioMode::MonitorOnly => {
+                self.set_source_audio_monitor(UNIQUE_REPLAY_SOURCE_NAME, MonitorType::MonitorOnly)
+                    .await
+            }
+            PlaybackAudioMode::Muted => {
+                self.set_source_muted(UNIQUE_REPLAY_SOURCE_NAME, true).await
+            }
+            PlaybackAudioMode::Normal => Ok(()),
+        };
+        if let Err(e) = res {
+            warn!("Failed to apply playback audio mode, skip: {}", e);
+            return None;
+        }
+        Some(prev)
+    }
+
+    pub async fn restore_audio_state(&self, prev: AudioState) {
+        if let Err(e) = self
+            .set_source_audio_monitor(UNIQUE_REPLAY_SOURCE_NAME, prev.monitor_type)
+            .await
+        {
+            warn!("{}", e);
+        }
+        if let Err(e) = self
+            .set_source_muted(UNIQUE_REPLAY_SOURCE_NAME, prev.muted)
+            .await
+        {
+            warn!("{}", e);
+        }
+    }
+
+    // VLCソースの再生が終わるまで待つ
+    // 再生が始まらないまま上限を過ぎるか、deadlineを過ぎたら終わったものとして扱う
+    pub async fn wait_vlc_playback_end(
+        &self,
+        source: &str,
+        deadline: &PlaybackDeadline,
+    ) -> Result<(), String> {
+        if let Some(mock) = &self.mock {
+            mock.wait_playback_end().await;
+            return Ok(());
+        }
+        let client = self.get_client()?;
+        let mut started = false;
+        let start_deadline = tokio::time::Instant::now() + PLAYBACK_START_TIMEOUT;
+        loop {
+            tokio::time::sleep(PLAYBACK_POLL_INTERVAL).await;
+            let now = tokio::time::Instant::now();
+            if !started && now >= start_deadline {
+                warn!(
+                    "{} did not start playing within {:?}, treat as ended",
+                    source, PLAYBACK_START_TIMEOUT
+                );
+                return Ok(());
+            }
+            if deadline
+                .lock()
+                .unwrap()
+                .is_some_and(|deadline| now >= deadline)
+            {
+                warn!(
+                    "{} is still playing past the expected end, treat as ended",
+                    source
+                );
+                return Ok(());
+            }
+            let status = self
+                .request(client.media_inputs().status(InputId::Name(source)))
+                .await
+                .map_err(|e| format!("Failed to get media status: {e}"))?;
+            match status.state {
+                MediaState::Playing | MediaState::Opening | MediaState::Buffering => started = true,
+                // 止めている間は期限を延ばす
+                MediaState::Paused => {
+                    if let Some(deadline) = deadline.lock().unwrap().as_mut() {
+                        *deadline += PLAYBACK_POLL_INTERVAL;
+                    }
+                }
+                _ if started => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
     async fn get_current_scene(
         &self,
     ) -> Result<obws::responses::scenes::CurrentProgramScene, String> {
         let client = self.get_client()?;
-        let current_scene = client.scenes().current_program_scene().await;
+        let current_scene = self.request(client.scenes().current_program_scene()).await;
         match current_scene {
             Ok(current_scene) => Ok(current_scene),
-            Err(_) => Err("Failed to get current scene".to_string()),
+            Err(e) => Err(format!("Failed to get current scene: {e}")),
         }
     }
 
-    async fn is_exit_vlc_soruce(&self) -> Result<bool, String> {
+    async fn is_exit_vlc_soruce(&self, source: &str) -> Result<bool, String> {
         let client = self.get_client()?;
-        let res = client
-            .inputs()
-            .list(Some(
+        let res = self
+            .request(client.inputs().list(Some(
                 obws::requests::custom::source_settings::SOURCE_VLC_SOURCE,
-            ))
+            )))
             .await;
         match res {
             Ok(inputs) => {
-                let is_exist = inputs
-                    .iter()
-                    .find(|&i| i.id.name == UNIQUE_REPLAY_SOURCE_NAME);
+                let is_exist = inputs.iter().find(|&i| i.id.name == source);
                 Ok(is_exist.is_some())
             }
             Err(_) => Err("Failed to get inputs".to_string()),
         }
     }
 
-    pub async fn set_event_listener(&self, tx: Sender<PathBuf>) -> Result<(), String> {
-        let host = self.host.get().unwrap();
-        let port = self.port.get().unwrap().to_owned();
-        let password = self.password.get().unwrap().as_ref().map(|d| d.as_str());
+    pub async fn set_event_listener(
+        &self,
+        tx: Sender<PathBuf>,
+        mut overflow: OverflowReporter,
+    ) -> Result<JoinHandle<()>, String> {
+        if let Some(mock) = &self.mock {
+            mock.set_replay_listener(tx);
+            return Ok(tokio::spawn(std::future::pending()));
+        }
+        let (Some(host), Some(port), Some(password), Some(secure)) = (
+            self.host.get(),
+            self.port.get(),
+            self.password.get(),
+            self.secure.get(),
+        ) else {
+            return Err("OBS is not connected".to_string());
+        };
 
-        let client = Client::connect(host, port, password).await.unwrap();
-        tokio::spawn(async move {
-            let events = client.events().unwrap();
+        // 接続できなくてもパニックせず、呼び出し側で再接続などを判断させる
+        let client = Self::connect_client(host, *port, password.as_deref(), *secure)
+            .await
+            .map_err(|e| format!("Failed to connect event listener: {e}"))?;
+        // 接続前から録画していることもあるので、イベントを待たずに今の状態を取る
+        if let Err(e) = self.update_recording_started_at().await {
+            warn!("{}", e);
+        }
+        let in_transition = self.in_transition.clone();
+        let recording_started_at = self.recording_started_at.clone();
+        let handle = tokio::spawn(async move {
+            let events = match client.events() {
+                Ok(events) => events,
+                Err(e) => {
+                    error!("Failed to subscribe to OBS events: {}", e);
+                    return;
+                }
+            };
             pin_mut!(events);
             while let Some(event) = events.next().await {
-                if let Event::ReplayBufferSaved { path } = event {
-                    tx.send(path).await.unwrap();
+                match event {
+                    Event::ReplayBufferSaved { path } => {
+                        // 受信側がいなくなったら終わる
+                        if !overflow.send(&tx, path) {
+                            return;
+                        }
+                    }
+                    Event::SceneTransitionStarted { .. } => *in_transition.write().unwrap() = true,
+                    Event::SceneTransitionEnded { .. } => *in_transition.write().unwrap() = false,
+                    Event::RecordStateChanged { state, .. } => match state {
+                        OutputState::Started => {
+                            *recording_started_at.write().unwrap() = Some(crate::unix_millis());
+                        }
+                        OutputState::Stopped => *recording_started_at.write().unwrap() = None,
+                        _ => {}
+                    },
+                    _ => {}
                 }
             }
         });
 
-        Ok(())
+        Ok(handle)
+    }
+}
+
+// Debug/JSON表記の中で秘密情報のフィールドの文字列値を***に置き換える
+fn redact_secrets(text: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('"') {
+        redacted.push_str(&rest[..start]);
+        let literal = &rest[start..];
+        let len = string_literal_len(literal);
+        if is_secret_field(&redacted) {
+            redacted.push_str("\"***\"");
+        } else {
+            redacted.push_str(&literal[..len]);
+        }
+        rest = &literal[len..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+// 先頭の"から閉じる"までの長さ（閉じていなければ残り全部）
+fn string_literal_len(literal: &str) -> usize {
+    let mut escaped = false;
+    for (i, c) in literal.char_indices().skip(1) {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return i + 1,
+            _ => escaped = false,
+        }
+    }
+    literal.len()
+}
+
+// 直前が`password: Some(`や`"streamKey": `の形か
+fn is_secret_field(preceding: &str) -> bool {
+    let name = preceding
+        .trim_end()
+        .trim_end_matches("Some(")
+        .trim_end()
+        .trim_end_matches(':')
+        .trim_end()
+        .trim_end_matches('"');
+    let name = name
+        .rsplit(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    !name.is_empty() && SECRET_FIELDS.iter().any(|field| name.contains(field))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_redact_secrets() {
+        // Debug表記
+        assert_eq!(
+            redact_secrets(r#"Connect { host: "localhost", password: Some("x") }"#),
+            r#"Connect { host: "localhost", password: Some("***") }"#
+        );
+        // JSON表記
+        assert_eq!(
+            redact_secrets(r#"{"server": "rtmp://a", "streamKey": "x"}"#),
+            r#"{"server": "rtmp://a", "streamKey": "***"}"#
+        );
+        assert_eq!(
+            redact_secrets(r#"{"name":"RecRBTime","value":"30"}"#),
+            r#"{"name":"RecRBTime","value":"30"}"#
+        );
+        // エスケープされた"で値が途中で切れない
+        assert_eq!(
+            redact_secrets(r#"{"password": "a\"b", "host": "c"}"#),
+            r#"{"password": "***", "host": "c"}"#
+        );
+        // 閉じていない値は残り全部を伏せる
+        assert_eq!(
+            redact_secrets(r#"password: Some("abc"#),
+            r#"password: Some("***""#
+        );
     }
 }
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+// 起動時に読む設定（logsと同じくカレントディレクトリ基準）
+const PREFERENCES_PATH: &str = "./preferences.json";
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct Preferences {
+    // 起動時に前回の接続先へ自動接続する
+    pub autoconnect: bool,
+    // save_credentialsか、autoconnectが有効なときの接続で保存する
+    pub connection: Option<StoredConnection>,
+    // 起動時の接続待ち（自動接続より前に効かせるのでここに保存する）
+    pub startup_delay_sec: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StoredConnection {
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub password: PasswordStorage,
+    pub secure: bool,
+}
+
+// パスワードの保存先
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(tag = "storage", rename_all = "snake_case")]
+pub enum PasswordStorage {
+    // パスワードなし
+    #[default]
+    None,
+    // OSのキーリング（このファイルには書かない）
+    Keyring,
+    // キーリングが使えない環境のみ。暗号化されない
+    Plaintext {
+        password: String,
+    },
+}
+
+// ファイルがなければ初期値
+pub fn load() -> Result<Preferences, String> {
+    let json = match std::fs::read_to_string(PREFERENCES_PATH) {
+        Ok(json) => json,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Preferences::default()),
+        Err(e) => return Err(format!("Failed to read preferences: {e}")),
+    };
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse preferences: {e}"))
+}
+
+pub fn save(preferences: &Preferences) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(preferences).map_err(|e| e.to_string())?;
+    std::fs::write(PREFERENCES_PATH, json).map_err(|e| format!("Failed to write preferences: {e}"))
+}
@@ -0,0 +1,88 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{Receiver, Sender};
+
+use crate::udp::UdpMessage;
+
+// 録画中のUDPコマンド（Noneなら録画していない）
+pub type SharedRecording = Arc<Mutex<Option<TriggerRecording>>>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedMessage {
+    // 録画開始からの経過時間
+    pub offset_ms: u64,
+    pub data: String,
+}
+
+pub struct TriggerRecording {
+    path: PathBuf,
+    started_at: Instant,
+    messages: Vec<RecordedMessage>,
+}
+
+impl TriggerRecording {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            started_at: Instant::now(),
+            messages: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, data: String) {
+        let offset_ms = self.started_at.elapsed().as_millis() as u64;
+        self.messages.push(RecordedMessage { offset_ms, data });
+    }
+
+    // 録画した内容をJSONで書き出して件数を返す
+    pub fn save(&self) -> Result<usize, String> {
+        let json = serde_json::to_string_pretty(&self.messages).map_err(|e| e.to_string())?;
+        std::fs::write(&self.path, json).map_err(|e| format!("Failed to write recording: {e}"))?;
+        Ok(self.messages.len())
+    }
+}
+
+pub fn load(path: &Path) -> Result<Vec<RecordedMessage>, String> {
+    let json =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read recording: {e}"))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse recording: {e}"))
+}
+
+// UDPから受け取ったメッセージを録画しつつメイン処理に流す
+pub async fn forward(
+    mut rx: Receiver<UdpMessage>,
+    tx: Sender<UdpMessage>,
+    recording: SharedRecording,
+) {
+    while let Some(msg) = rx.recv().await {
+        if let Some(recording) = recording.lock().unwrap().as_mut() {
+            recording.push(msg.data.clone());
+        }
+        if tx.send(msg).await.is_err() {
+            return;
+        }
+    }
+}
+
+// 録画時と同じ間隔でメッセージを流し込む
+pub async fn replay(messages: Vec<RecordedMessage>, tx: Sender<UdpMessage>) {
+    let started_at = tokio::time::Instant::now();
+    let count = messages.len();
+    for message in messages {
+        tokio::time::sleep_until(started_at + Duration::from_millis(message.offset_ms)).await;
+        let msg = UdpMessage {
+            data: message.data,
+            source: None,
+            port: None,
+        };
+        if let Err(e) = tx.send(msg).await {
+            error!("Failed to replay trigger: {}", e);
+            return;
+        }
+    }
+    info!("Replayed {} recorded messages", count);
+}
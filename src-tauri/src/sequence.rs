@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::mugi_schema::MugiCmd;
+
+// 順番に届いたら1回だけ保存するコマンドの並び
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TriggerSequence {
+    pub steps: Vec<MugiCmd>,
+    // 最初のコマンドからこの秒数以内に最後まで揃うこと
+    pub within_sec: u64,
+    // 指定すると全てのコマンドでこのキー(player等)の値が同じときだけ揃ったとみなす
+    pub same_key: Option<String>,
+}
+
+impl TriggerSequence {
+    // タグ用 (例: "EpicSave+Scored")
+    pub fn label(&self) -> String {
+        let steps: Vec<String> = self.steps.iter().map(|cmd| format!("{:?}", cmd)).collect();
+        steps.join("+")
+    }
+}
+
+// 途中まで揃った並び
+struct Partial {
+    started_at: Instant,
+    matched: usize,
+    key_value: Option<String>,
+}
+
+// 受信したコマンドを順に流し込んで、並びが揃ったかを判定する
+#[derive(Default)]
+pub struct SequenceMatcher {
+    sequence: Option<TriggerSequence>,
+    partials: Vec<Partial>,
+}
+
+impl SequenceMatcher {
+    // 揃ったらtrue。途中まで揃ったものは時間切れで黙って捨てる
+    pub fn feed(
+        &mut self,
+        sequence: Option<&TriggerSequence>,
+        cmd: MugiCmd,
+        tags: &HashMap<String, String>,
+        now: Instant,
+    ) -> bool {
+        // 並びが変わったら途中経過を捨てる
+        if self.sequence.as_ref() != sequence {
+            self.sequence = sequence.cloned();
+            self.partials.clear();
+        }
+        let Some(sequence) = &self.sequence else {
+            return false;
+        };
+        let Some(&first) = sequence.steps.first() else {
+            return false;
+        };
+        let window = Duration::from_secs(sequence.within_sec);
+        // キーの値がないコマンドは同じかどうか分からないので、並びに数えない
+        let key_value = match &sequence.same_key {
+            Some(key) => match tags.get(key) {
+                Some(value) => Some(value.clone()),
+                None => return false,
+            },
+            None => None,
+        };
+
+        self.partials
+            .retain(|partial| now.duration_since(partial.started_at) <= window);
+        for partial in &mut self.partials {
+            if sequence.steps[partial.matched] == cmd && partial.key_value == key_value {
+                partial.matched += 1;
+            }
+        }
+        if cmd == first {
+            self.partials.push(Partial {
+                started_at: now,
+                matched: 1,
+                key_value,
+            });
+        }
+        let completed = self
+            .partials
+            .iter()
+            .any(|partial| partial.matched == sequence.steps.len());
+        if completed {
+            self.partials.clear();
+        }
+        completed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sequence(same_key: Option<&str>) -> TriggerSequence {
+        TriggerSequence {
+            steps: vec![MugiCmd::EpicSave, MugiCmd::Scored],
+            within_sec: 5,
+            same_key: same_key.map(str::to_string),
+        }
+    }
+
+    fn tags(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_in_order() {
+        let seq = sequence(None);
+        let mut matcher = SequenceMatcher::default();
+        let now = Instant::now();
+        assert!(!matcher.feed(Some(&seq), MugiCmd::Scored, &tags(&[]), now));
+        assert!(!matcher.feed(Some(&seq), MugiCmd::EpicSave, &tags(&[]), now));
+        assert!(matcher.feed(
+            Some(&seq),
+            MugiCmd::Scored,
+            &tags(&[]),
+            now + Duration::from_secs(1)
+        ));
+        // 揃ったら最初からやり直す
+        assert!(!matcher.feed(
+            Some(&seq),
+            MugiCmd::Scored,
+            &tags(&[]),
+            now + Duration::from_secs(2)
+        ));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let seq = sequence(None);
+        let mut matcher = SequenceMatcher::default();
+        let now = Instant::now();
+        assert!(!matcher.feed(Some(&seq), MugiCmd::EpicSave, &tags(&[]), now));
+        assert!(!matcher.feed(
+            Some(&seq),
+            MugiCmd::Scored,
+            &tags(&[]),
+            now + Duration::from_secs(6)
+        ));
+    }
+
+    #[test]
+    fn test_interleaved() {
+        let seq = sequence(None);
+        let mut matcher = SequenceMatcher::default();
+        let now = Instant::now();
+        assert!(!matcher.feed(Some(&seq), MugiCmd::EpicSave, &tags(&[]), now));
+        // 関係ないコマンドが挟まっても途中経過は残る
+        assert!(!matcher.feed(Some(&seq), MugiCmd::Demolished, &tags(&[]), now));
+        assert!(matcher.feed(
+            Some(&seq),
+            MugiCmd::Scored,
+            &tags(&[]),
+            now + Duration::from_secs(4)
+        ));
+    }
+
+    #[test]
+    fn test_same_key() {
+        let seq = sequence(Some("player"));
+        let mut matcher = SequenceMatcher::default();
+        let now = Instant::now();
+        let alice = tags(&[("player", "alice")]);
+        let bob = tags(&[("player", "bob")]);
+        assert!(!matcher.feed(Some(&seq), MugiCmd::EpicSave, &alice, now));
+        assert!(!matcher.feed(Some(&seq), MugiCmd::Scored, &bob, now));
+        assert!(matcher.feed(Some(&seq), MugiCmd::Scored, &alice, now));
+
+        // キーのないコマンド同士は揃ったとみなさない
+        assert!(!matcher.feed(Some(&seq), MugiCmd::EpicSave, &tags(&[]), now));
+        assert!(!matcher.feed(Some(&seq), MugiCmd::Scored, &tags(&[]), now));
+    }
+}
@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::events::AppEvent;
+use crate::mugi_schema::MugiCmd;
+
+// セッションフォルダの置き場所（logsと同じくカレントディレクトリ基準）
+const SESSIONS_DIR: &str = "./sessions";
+const MANIFEST_FILE_NAME: &str = "session.json";
+const MARKERS_FILE_NAME: &str = "markers.json";
+
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ClipKind {
+    Video,
+    // スクリーンショット
+    Image,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SessionClip {
+    pub path: PathBuf,
+    pub kind: ClipKind,
+    // 手動保存はNone
+    pub event: Option<MugiCmd>,
+    // unix time(ms)
+    pub triggered_at: u64,
+    pub delay_ms: u64,
+}
+
+// 連続録画モードでイベントの位置を示すマーカー
+#[derive(Serialize, Debug, Clone)]
+pub struct SessionMarker {
+    // unix time(ms)
+    pub timestamp: u64,
+    // 録画開始からの経過時間（録画の開始時刻が分からなければセッション開始から）
+    pub offset_ms: u64,
+    pub event: MugiCmd,
+    pub label: String,
+}
+
+impl AppEvent for SessionMarker {
+    const NAME: &'static str = "marker-added";
+}
+
+// キャプチャセッション1回分の記録
+#[derive(Serialize, Debug, Clone)]
+pub struct SessionManifest {
+    // unix time(ms)
+    pub started_at: u64,
+    pub ended_at: Option<u64>,
+    pub obs_host: String,
+    pub clips: Vec<SessionClip>,
+    pub markers: Vec<SessionMarker>,
+}
+
+impl SessionManifest {
+    pub fn new(obs_host: String, started_at: u64) -> Self {
+        Self {
+            started_at,
+            ended_at: None,
+            obs_host,
+            clips: Vec::new(),
+            markers: Vec::new(),
+        }
+    }
+
+    pub fn folder(&self) -> PathBuf {
+        PathBuf::from(SESSIONS_DIR).join(format!("session_{}", self.started_at))
+    }
+
+    // マーカーを追加してmarkers.jsonを書き直す
+    pub fn add_marker(&mut self, marker: SessionMarker) -> Result<PathBuf, String> {
+        self.markers.push(marker);
+        let folder = self.folder();
+        std::fs::create_dir_all(&folder)
+            .map_err(|e| format!("Failed to create session folder: {e}"))?;
+        let path = folder.join(MARKERS_FILE_NAME);
+        let json = serde_json::to_string_pretty(&self.markers).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write session markers: {e}"))?;
+        Ok(path)
+    }
+
+    // 終了時刻を記録してsession.jsonを書き出す
+    pub fn finish(&mut self, ended_at: u64) -> Result<PathBuf, String> {
+        self.ended_at = Some(ended_at);
+        let folder = self.folder();
+        std::fs::create_dir_all(&folder)
+            .map_err(|e| format!("Failed to create session folder: {e}"))?;
+        let path = folder.join(MANIFEST_FILE_NAME);
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json)
+            .map_err(|e| format!("Failed to write session manifest: {e}"))?;
+        Ok(path)
+    }
+}
+
+// ペース確認用の集計（試合開始・システム再起動でリセット）
+#[derive(Debug, Clone, Copy)]
+pub struct SessionCounter {
+    started_at: Instant,
+    clip_count: usize,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SessionStats {
+    pub elapsed_sec: u64,
+    pub clip_count: usize,
+    pub clips_per_minute: f64,
+}
+
+impl Default for SessionCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SessionCounter {
+    pub fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            clip_count: 0,
+        }
+    }
+
+    pub fn add_clip(&mut self) {
+        self.clip_count += 1;
+    }
+
+    pub fn stats(&self) -> SessionStats {
+        let elapsed = self.started_at.elapsed();
+        let minutes = elapsed.as_secs_f64() / 60.0;
+        let clips_per_minute = if minutes > 0.0 {
+            self.clip_count as f64 / minutes
+        } else {
+            0.0
+        };
+        SessionStats {
+            elapsed_sec: elapsed.as_secs(),
+            clip_count: self.clip_count,
+            clips_per_minute,
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::mugi_schema::MugiCmd;
+
+// クリップの横に置くメタデータ(sidecar)
+// 古いsidecarはlabelしかないのでdefaultで読む
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct ClipSidecar {
+    pub label: Option<String>,
+    // 手動保存はNone
+    pub event: Option<MugiCmd>,
+    // unix time(ms)
+    pub timestamp: Option<u64>,
+    pub delay_ms: Option<u64>,
+    // コマンドのデータ(player, clockなど)とユーザーが付けたタグ
+    pub tags: HashMap<String, String>,
+}
+
+// clip.mkv -> clip.json
+pub fn sidecar_path(clip_path: &Path) -> PathBuf {
+    clip_path.with_extension("json")
+}
+
+// sidecarがなければ空のメタデータを返す
+pub fn read_sidecar(clip_path: &Path) -> Result<ClipSidecar, String> {
+    let path = sidecar_path(clip_path);
+    if !path.exists() {
+        return Ok(ClipSidecar::default());
+    }
+    let json = std::fs::read_to_string(path).map_err(|e| format!("Failed to read sidecar: {e}"))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse sidecar: {e}"))
+}
+
+pub fn write_sidecar(clip_path: &Path, sidecar: &ClipSidecar) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(sidecar).map_err(|e| e.to_string())?;
+    std::fs::write(sidecar_path(clip_path), json)
+        .map_err(|e| format!("Failed to write sidecar: {e}"))
+}
@@ -1,18 +1,291 @@
-use log::info;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::net::UdpSocket;
 use tokio::sync::mpsc::Sender;
+
+use crate::events::{AppEvent, EventSender, OverflowReporter};
 // use tauri::async_runtime::{Receiver,Sender};
 
-pub async fn bind_socket(tx: Sender<String>) -> io::Result<()> {
-    let sock = UdpSocket::bind("0.0.0.0:12344").await?;
-    info!("Listening on {}", sock.local_addr()?);
+// 連続でbindに失敗したら諦める回数
+const MAX_RESTART_ATTEMPTS: u32 = 5;
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+// これ以上動いていたら一時的な障害とみなして試行回数をリセット
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+pub const DEFAULT_BIND_ADDR: &str = "0.0.0.0:12344";
+// 組み立て途中のコマンドの上限（送信元ごと）
+const MAX_FRAGMENT_BYTES: usize = 4096;
+// 組み立て途中のバッファを持つ送信元の上限（超えたら一番古いものを捨てる）
+const MAX_FRAGMENT_SOURCES: usize = 64;
+// これだけ続きが来なければ組み立て途中のバッファを捨てる
+const FRAGMENT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// データグラムとコマンドの区切り方
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum UdpFraming {
+    // 1データグラム=1コマンド
+    #[default]
+    Datagram,
+    // 改行区切り（データグラムをまたいで分割されていても組み立てる）
+    Newline,
+}
+
+#[derive(Debug, Clone)]
+pub struct UdpMessage {
+    pub data: String,
+    // 送信元（録画の再生など、UDP以外から来たものはNone）
+    pub source: Option<IpAddr>,
+    // 受信したローカルのポート
+    pub port: Option<u16>,
+}
+
+// 組み立て途中のコマンドと最後に受信した時刻
+struct Fragment {
+    data: Vec<u8>,
+    last_seen: Instant,
+}
+
+// 改行区切りのコマンドを送信元ごとに組み立てる
+#[derive(Default)]
+struct Reassembler {
+    buffers: HashMap<SocketAddr, Fragment>,
+}
+
+impl Reassembler {
+    // 完成したコマンドを返す（区切りの来ていない残りは次のデータグラムまで持っておく）
+    fn push(&mut self, source: SocketAddr, data: &[u8], now: Instant) -> Vec<String> {
+        self.evict(source, now);
+        let fragment = self.buffers.entry(source).or_insert_with(|| Fragment {
+            data: Vec::new(),
+            last_seen: now,
+        });
+        fragment.last_seen = now;
+        let buffer = &mut fragment.data;
+        buffer.extend_from_slice(data);
+
+        let mut commands = Vec::new();
+        while let Some(pos) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=pos).collect();
+            match std::str::from_utf8(&line) {
+                Ok(line) => {
+                    let line = line.trim_end_matches(['\r', '\n']);
+                    if !line.is_empty() {
+                        commands.push(line.to_string());
+                    }
+                }
+                Err(e) => warn!("Drop invalid UTF-8 command from {}: {}", source, e),
+            }
+        }
+
+        if buffer.len() > MAX_FRAGMENT_BYTES {
+            warn!(
+                "Fragment from {} exceeded {} bytes, discarded",
+                source, MAX_FRAGMENT_BYTES
+            );
+            self.buffers.remove(&source);
+        } else if buffer.is_empty() {
+            self.buffers.remove(&source);
+        }
+        commands
+    }
+
+    // 続きの来ないバッファを捨てて、新しい送信元の分が空かなければ一番古いものを捨てる
+    fn evict(&mut self, source: SocketAddr, now: Instant) {
+        self.buffers.retain(|addr, fragment| {
+            let fresh = now.duration_since(fragment.last_seen) < FRAGMENT_IDLE_TIMEOUT;
+            if !fresh {
+                warn!("Discard stale fragment from {}", addr);
+            }
+            fresh
+        });
+        if !self.buffers.contains_key(&source)
+            && self.buffers.len() >= MAX_FRAGMENT_SOURCES
+            && let Some(oldest) = self
+                .buffers
+                .iter()
+                .min_by_key(|(_, fragment)| fragment.last_seen)
+                .map(|(source, _)| *source)
+        {
+            warn!(
+                "Too many fragmented senders, discard fragment from {}",
+                oldest
+            );
+            self.buffers.remove(&oldest);
+        }
+    }
+}
+
+// 受信側が閉じたらOk(())で終わる
+pub async fn bind_socket(
+    addr: &str,
+    tx: Sender<UdpMessage>,
+    framing: UdpFraming,
+    mut overflow: OverflowReporter,
+) -> io::Result<()> {
+    let sock = UdpSocket::bind(addr).await?;
+    let local_addr = sock.local_addr()?;
+    info!("Listening on {}", local_addr);
     let mut buf = [0; 1024];
+    let mut reassembler = Reassembler::default();
     // let mut f = File::create("mugi_log.txt").await?;
     loop {
-        let (size, _addr) = sock.recv_from(&mut buf).await?;
-        let data = std::str::from_utf8(&buf[..size]).unwrap();
-        let d = data.to_string();
-        tx.send(d).await.unwrap();
+        let (size, addr) = sock.recv_from(&mut buf).await?;
+        let commands = match framing {
+            UdpFraming::Datagram => match std::str::from_utf8(&buf[..size]) {
+                Ok(data) => vec![data.to_string()],
+                Err(e) => {
+                    warn!("Drop invalid UTF-8 command from {}: {}", addr, e);
+                    continue;
+                }
+            },
+            UdpFraming::Newline => reassembler.push(addr, &buf[..size], Instant::now()),
+        };
+        for data in commands {
+            let d = UdpMessage {
+                data,
+                source: Some(addr.ip()),
+                port: Some(local_addr.port()),
+            };
+            // 処理が追いつかないときに受信を止めるとOS側で黙って捨てられるので、ここで数えて捨てる
+            if !overflow.send(&tx, d) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+struct UdpRestartedPayload {
+    attempt: u32,
+    error: String,
+}
+
+impl AppEvent for UdpRestartedPayload {
+    const NAME: &'static str = "udp-restarted";
+}
+
+// 再bindを諦めたときのエラー
+#[derive(Serialize, Clone)]
+struct UdpFailedPayload(String);
+
+impl AppEvent for UdpFailedPayload {
+    const NAME: &'static str = "udp-failed";
+}
+
+// bind_socketが落ちたらバックオフしながら再bindする
+pub async fn supervise_socket(
+    addr: String,
+    tx: Sender<UdpMessage>,
+    framing: UdpFraming,
+    events: EventSender,
+    dropped: Arc<Mutex<u64>>,
+) {
+    let mut attempt = 0;
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        let started = Instant::now();
+        let e = match bind_socket(
+            &addr,
+            tx.clone(),
+            framing,
+            OverflowReporter::new("udp", dropped.clone(), events.clone()),
+        )
+        .await
+        {
+            Ok(()) => return,
+            Err(e) => e,
+        };
+        error!("UDP socket error: {}", e);
+
+        if started.elapsed() >= STABLE_RUN {
+            attempt = 0;
+            backoff = Duration::from_secs(1);
+        }
+        attempt += 1;
+        if attempt > MAX_RESTART_ATTEMPTS {
+            error!(
+                "UDP socket failed {} times in a row, giving up",
+                MAX_RESTART_ATTEMPTS
+            );
+            events.emit(UdpFailedPayload(e.to_string()));
+            return;
+        }
+
+        warn!(
+            "Restarting UDP socket in {:?} (attempt {})",
+            backoff, attempt
+        );
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+
+        let payload = UdpRestartedPayload {
+            attempt,
+            error: e.to_string(),
+        };
+        events.emit(payload);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn test_reassembler() {
+        let mut reassembler = Reassembler::default();
+        let now = Instant::now();
+
+        // データグラムをまたいだコマンド
+        assert!(reassembler.push(addr(1), b"{\"cmd\":", now).is_empty());
+        assert_eq!(
+            reassembler.push(addr(1), b"\"goals\"}\r\n", now),
+            vec![r#"{"cmd":"goals"}"#]
+        );
+
+        // 1データグラムに複数のコマンド（残りは次まで持つ）
+        assert_eq!(reassembler.push(addr(1), b"a\n\nb\nc", now), vec!["a", "b"]);
+        assert_eq!(reassembler.push(addr(1), b"\n", now), vec!["c"]);
+        assert!(reassembler.buffers.is_empty());
+
+        // 上限を超えたら捨てる
+        let long = vec![b'x'; MAX_FRAGMENT_BYTES + 1];
+        assert!(reassembler.push(addr(1), &long, now).is_empty());
+        assert!(reassembler.buffers.is_empty());
+
+        // 続きの来ないバッファは捨てる
+        reassembler.push(addr(1), b"old", now);
+        let later = now + FRAGMENT_IDLE_TIMEOUT;
+        assert_eq!(reassembler.push(addr(1), b"new\n", later), vec!["new"]);
+    }
+
+    #[test]
+    fn test_reassembler_source_cap() {
+        let mut reassembler = Reassembler::default();
+        let now = Instant::now();
+        for i in 0..MAX_FRAGMENT_SOURCES {
+            let at = now + Duration::from_millis(i as u64);
+            reassembler.push(addr(i as u16), b"x", at);
+        }
+        assert_eq!(reassembler.buffers.len(), MAX_FRAGMENT_SOURCES);
+
+        // 新しい送信元は受け付けて、一番古いものを捨てる
+        let at = now + Duration::from_secs(1);
+        reassembler.push(addr(1000), b"y", at);
+        assert_eq!(reassembler.buffers.len(), MAX_FRAGMENT_SOURCES);
+        assert!(!reassembler.buffers.contains_key(&addr(0)));
+        assert_eq!(reassembler.push(addr(1000), b"\n", at), vec!["y"]);
+
+        // 既にある送信元の続きでは何も捨てない
+        assert_eq!(reassembler.push(addr(1), b"\n", at), vec!["x"]);
+        assert_eq!(reassembler.buffers.len(), MAX_FRAGMENT_SOURCES - 2);
     }
 }
@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use log::warn;
+
+const FILE_NAME: &str = "ui_preferences.json";
+// 際限なく増えないように制限する
+const MAX_KEYS: usize = 256;
+const MAX_KEY_LEN: usize = 64;
+const MAX_VALUE_BYTES: usize = 64 * 1024;
+
+// フロントエンド用のkey/value設定（アプリのデータフォルダに保存する）
+#[derive(Debug, Default)]
+pub struct UiPreferences {
+    // Noneなら保存しない（setup前）
+    path: Option<PathBuf>,
+    values: BTreeMap<String, serde_json::Value>,
+}
+
+impl UiPreferences {
+    // 読めなければ空で始める
+    pub fn load(dir: PathBuf) -> Self {
+        let path = dir.join(FILE_NAME);
+        let values = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!("Failed to parse {:?}: {}", path, e);
+                BTreeMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                warn!("Failed to read {:?}: {}", path, e);
+                BTreeMap::new()
+            }
+        };
+        Self {
+            path: Some(path),
+            values,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<serde_json::Value> {
+        self.values.get(key).cloned()
+    }
+
+    // nullを入れるとキーを消す
+    pub fn set(&mut self, key: String, value: serde_json::Value) -> Result<(), String> {
+        validate_key(&key)?;
+        if value.is_null() {
+            self.values.remove(&key);
+            return self.save();
+        }
+        let size = value.to_string().len();
+        if size > MAX_VALUE_BYTES {
+            return Err(format!("値が大きすぎます({}バイトまで)", MAX_VALUE_BYTES));
+        }
+        if !self.values.contains_key(&key) && self.values.len() >= MAX_KEYS {
+            return Err(format!("設定は{}個までです", MAX_KEYS));
+        }
+        self.values.insert(key, value);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Err("設定の保存先が初期化されていません".to_string());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create app data folder: {e}"))?;
+        }
+        let json = serde_json::to_string_pretty(&self.values).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("Failed to write preferences: {e}"))
+    }
+}
+
+// 英数字と . _ - のみ
+fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        return Err(format!("キーは1〜{}文字にしてください", MAX_KEY_LEN));
+    }
+    if !key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    {
+        return Err(format!("キーに使えない文字が含まれています: {}", key));
+    }
+    Ok(())
+}
@@ -1,25 +1,323 @@
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use log::{error, info};
-use tauri::Emitter;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::Receiver;
+use tokio::sync::oneshot;
 
-pub struct VlcManager {}
+use crate::events::{AppEvent, EventSender};
+use crate::ffmpeg;
+use crate::obs::Obs;
+
+// 保存済みクリップのパス一覧
+pub type ClipList = Arc<Mutex<Vec<PathBuf>>>;
+
+// 次に保存されたリプレイの扱い
+pub enum NextReplay {
+    // トリガーごとの保存先に移す
+    MoveTo(PathBuf),
+    // 計測用なので一覧に入れずに消す
+    Discard,
+}
+
+// 次に保存されたリプレイの扱いと、扱った後のパスの返し先
+// 保存はsave_lockで1つずつ行うので、頼んだ直後の保存が対象になる
+pub type ReplayRedirect = Arc<Mutex<Option<(NextReplay, oneshot::Sender<PathBuf>)>>>;
+
+// folderに移して移動先を返す。ドライブが違えばコピーしてから消す
+async fn move_clip(path: &Path, folder: &Path) -> Result<PathBuf, String> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("ファイル名がありません: {}", path.display()))?;
+    tokio::fs::create_dir_all(folder)
+        .await
+        .map_err(|e| format!("Failed to create {}: {e}", folder.display()))?;
+    let dest = folder.join(file_name);
+    if tokio::fs::rename(path, &dest).await.is_err() {
+        tokio::fs::copy(path, &dest).await.map_err(|e| {
+            format!(
+                "Failed to move {} to {}: {e}",
+                path.display(),
+                folder.display()
+            )
+        })?;
+        if let Err(e) = tokio::fs::remove_file(path).await {
+            warn!("Failed to remove {} after copying: {}", path.display(), e);
+        }
+    }
+    Ok(dest)
+}
+
+// OBSが出力できてVLCソースで再生できる拡張子
+pub const SUPPORTED_EXTENSIONS: &[&str] = &["mkv", "mp4", "mov", "flv", "ts", "m4v", "webm"];
+
+pub fn validate_clip_path(path: &Path) -> Result<(), String> {
+    if !path.is_file() {
+        return Err(format!("ファイルが見つかりません: {}", path.display()));
+    }
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !SUPPORTED_EXTENSIONS.contains(&ext.as_str()) {
+        return Err(format!("対応していない形式です: {}", path.display()));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Clone)]
+struct ClipInvalidPayload {
+    path: PathBuf,
+    error: String,
+}
+
+impl AppEvent for ClipInvalidPayload {
+    const NAME: &'static str = "clip-invalid";
+}
+
+// 再生リストに追加したクリップのパス
+#[derive(Serialize, Clone)]
+struct VideoPathAddedPayload(PathBuf);
+
+impl AppEvent for VideoPathAddedPayload {
+    const NAME: &'static str = "video_path_added";
+}
+
+// VLCソースで再生できないことがある形式の警告
+#[derive(Serialize, Debug, Clone)]
+pub struct FormatWarning {
+    pub path: PathBuf,
+    pub reason: String,
+    pub suggestion: String,
+}
+
+pub fn check_playback_format(path: &Path) -> Option<FormatWarning> {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !matches!(ext.as_str(), "mp4" | "mov" | "m4v") {
+        return None;
+    }
+    match is_fragmented_mp4(path) {
+        Ok(true) => Some(FormatWarning {
+            path: path.to_path_buf(),
+            reason: "断片化MP4/MOVはVLCソースで再生できないことがあります".to_string(),
+            suggestion: "OBSの録画フォーマットをmkvにしてください".to_string(),
+        }),
+        Ok(false) => None,
+        Err(e) => {
+            warn!("Failed to inspect {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+// トップレベルにmoofボックスがあれば断片化MP4
+fn is_fragmented_mp4(path: &Path) -> std::io::Result<bool> {
+    let mut file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut pos = 0;
+    // 先頭のボックスだけ見れば十分
+    for _ in 0..64 {
+        if pos + 8 > len {
+            break;
+        }
+        let mut header = [0; 8];
+        file.read_exact(&mut header)?;
+        let mut size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let box_type = &header[4..8];
+        if box_type == b"moof" {
+            return Ok(true);
+        }
+        if size == 1 {
+            let mut large = [0; 8];
+            file.read_exact(&mut large)?;
+            size = u64::from_be_bytes(large);
+        }
+        // 0は末尾まで
+        if size < 8 {
+            break;
+        }
+        pos += size;
+        file.seek(SeekFrom::Start(pos))?;
+    }
+    Ok(false)
+}
+
+const PLAYLISTS_FILE_NAME: &str = "playlists.json";
+
+// 名前付きの再生リスト（アプリのデータフォルダに保存する）
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct NamedPlaylists {
+    // Noneなら保存しない（setup前）
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    pub playlists: BTreeMap<String, Vec<PathBuf>>,
+    // Noneならplay_highlightsに渡されたクリップを再生する
+    pub active: Option<String>,
+}
+
+impl NamedPlaylists {
+    // 読めなければ空で始める
+    pub fn load(dir: PathBuf) -> Self {
+        let path = dir.join(PLAYLISTS_FILE_NAME);
+        let playlists = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                warn!("Failed to parse {:?}: {}", path, e);
+                Self::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                warn!("Failed to read {:?}: {}", path, e);
+                Self::default()
+            }
+        };
+        Self {
+            path: Some(path),
+            ..playlists
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<&[PathBuf], String> {
+        self.playlists
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| format!("再生リスト{}が見つかりません", name))
+    }
+
+    pub fn active_clips(&self) -> Option<&[PathBuf]> {
+        let name = self.active.as_ref()?;
+        self.playlists.get(name).map(Vec::as_slice)
+    }
+
+    // 同名のリストは上書き
+    pub fn insert(&mut self, name: String, clips: Vec<PathBuf>) -> Result<(), String> {
+        self.playlists.insert(name, clips);
+        self.save()
+    }
+
+    // Noneで解除
+    pub fn select(&mut self, name: Option<String>) -> Result<(), String> {
+        if let Some(name) = &name {
+            self.get(name)?;
+        }
+        self.active = name;
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Err("再生リストの保存先が初期化されていません".to_string());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create app data folder: {e}"))?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| format!("Failed to write playlists: {e}"))
+    }
+}
+
+pub struct VlcManager {
+    clips: ClipList,
+    redirect: ReplayRedirect,
+    // 再生できるか確認してから一覧に入れる
+    verify_clips: bool,
+}
 
 impl VlcManager {
-    pub fn new() -> Self {
-        Self {}
+    // 比較再生で左右に並べるVLCソース。通常の再生用とは別に、初回の比較再生で作る
+    pub const DUAL_SOURCES: [&'static str; 2] =
+        ["RL_REPLAY_VLC_SOURCE_LEFT", "RL_REPLAY_VLC_SOURCE_RIGHT"];
+
+    pub fn new(clips: ClipList, redirect: ReplayRedirect, verify_clips: bool) -> Self {
+        Self {
+            clips,
+            redirect,
+            verify_clips,
+        }
+    }
+    // 再生を始めたソースを最初のフレームでfreezeの間止めてから再生を続ける
+    pub async fn freeze_intro(obs: &Obs, sources: &[&str], freeze: Duration) {
+        for source in sources {
+            // 読み込み中は一時停止が効かないので、再生が始まるまで待つ
+            if let Err(e) = obs.wait_vlc_playing(source).await {
+                warn!("{}", e);
+                continue;
+            }
+            if let Err(e) = obs.pause_vlc_source(source).await {
+                warn!("{}", e);
+            }
+        }
+        tokio::time::sleep(freeze).await;
+        for source in sources {
+            if let Err(e) = obs.resume_vlc_source(source).await {
+                warn!("{}", e);
+            }
+        }
     }
+
     // replay_bufferのpathをフロントエンドに送信
     // rx: OBSのreplay_bufferのpathが降ってくる
-    pub fn set_event_listener(&self, mut rx: Receiver<PathBuf>, app_handle: tauri::AppHandle) {
+    pub fn set_event_listener(&self, mut rx: Receiver<PathBuf>, events: EventSender) {
+        let clips = self.clips.clone();
+        let redirect = self.redirect.clone();
+        let verify_clips = self.verify_clips;
         tokio::spawn(async move {
+            // ffprobeがなければ確認できないので、確認せずに一覧に入れる
+            let verify_clips = verify_clips && {
+                let available = ffmpeg::is_ffprobe_available().await;
+                if !available {
+                    warn!("ffprobe not found, clips are added without verification");
+                }
+                available
+            };
             while let Some(path) = rx.recv().await {
                 info!("path:{:?}", path);
-                // フロントエンドに個別のパスを送信
-                if let Err(e) = app_handle.emit("video_path_added", path) {
-                    error!("Failed to emit video_path_added event: {}", e);
+                // 扱いを頼まれていれば、一覧に入れる前に済ませる
+                let redirect_to = redirect.lock().unwrap().take();
+                let path = match redirect_to {
+                    Some((NextReplay::Discard, tx)) => {
+                        if let Err(e) = tokio::fs::remove_file(&path).await
+                            && e.kind() != std::io::ErrorKind::NotFound
+                        {
+                            warn!("Failed to remove {:?}: {}", path, e);
+                        }
+                        let _ = tx.send(path);
+                        continue;
+                    }
+                    Some((NextReplay::MoveTo(folder), tx)) => {
+                        let path = match move_clip(&path, &folder).await {
+                            Ok(moved) => {
+                                info!("Moved {:?} to {:?}", path, moved);
+                                moved
+                            }
+                            Err(e) => {
+                                warn!("{}", e);
+                                path
+                            }
+                        };
+                        let _ = tx.send(path.clone());
+                        path
+                    }
+                    None => path,
+                };
+                // 壊れたファイルは再生リストに入れない
+                if verify_clips && let Err(e) = ffmpeg::probe_first_frame(&path).await {
+                    warn!("Skip invalid clip {:?}: {}", path, e);
+                    let payload = ClipInvalidPayload { path, error: e };
+                    events.emit(payload);
+                    continue;
                 }
+                clips.lock().unwrap().push(path.clone());
+                // フロントエンドに個別のパスを送信
+                events.emit(VideoPathAddedPayload(path));
             }
         });
     }
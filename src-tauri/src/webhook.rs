@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::{debug, error};
+use serde::Serialize;
+
+use crate::events::{AppEvent, EventSender};
+use crate::mugi_schema::MugiCmd;
+
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Serialize, Debug)]
+pub struct WebhookPayload {
+    pub event: MugiCmd,
+    pub path: PathBuf,
+    // unix time(ms)
+    pub timestamp: u64,
+}
+
+// 送信に失敗したときのエラー
+#[derive(Serialize, Clone)]
+struct WebhookFailedPayload(String);
+
+impl AppEvent for WebhookFailedPayload {
+    const NAME: &'static str = "webhook-failed";
+}
+
+pub fn validate_url(url: &str) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid webhook URL: {e}"))?;
+    match parsed.scheme() {
+        "http" | "https" => Ok(()),
+        scheme => Err(format!("Unsupported webhook scheme: {scheme}")),
+    }
+}
+
+// キャプチャループを止めないように別タスクで送信する
+pub fn notify(url: String, payload: WebhookPayload, events: EventSender) {
+    tokio::spawn(async move {
+        match post(&url, &payload).await {
+            Ok(_) => debug!("Webhook sent: {:?}", payload),
+            Err(e) => {
+                error!("Webhook failed: {}", e);
+                events.emit(WebhookFailedPayload(e));
+            }
+        }
+    });
+}
+
+async fn post(url: &str, payload: &WebhookPayload) -> Result<(), String> {
+    let client = reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    client
+        .post(url)
+        .json(payload)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,59 @@
+import { useState, useEffect } from "react";
+import { invoke, convertFileSrc } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+
+// OBSを通さずにクリップを再生するウィンドウ
+function HighlightWindow() {
+  const [clipPath, setClipPath] = useState<string | null>(null);
+
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let cancelled = false;
+
+    listen<{ path: string | null }>("highlight-window-clip", (event) => {
+      setClipPath(event.payload.path);
+    }).then((unlistenFn) => {
+      // StrictModeで2回マウントされたときに1本目を飛ばさない
+      if (cancelled) {
+        unlistenFn();
+        return;
+      }
+      unlisten = unlistenFn;
+      // 準備ができたら最初のクリップを要求する
+      invoke("window_next").catch((error) => {
+        console.error("Failed to get next clip:", error);
+      });
+    });
+
+    return () => {
+      cancelled = true;
+      if (unlisten) {
+        unlisten();
+      }
+    };
+  }, []);
+
+  const handleEnded = async () => {
+    try {
+      await invoke("window_next");
+    } catch (error) {
+      console.error("Failed to get next clip:", error);
+    }
+  };
+
+  return (
+    <div className="h-screen bg-black flex items-center justify-center">
+      {clipPath && (
+        <video
+          key={clipPath}
+          src={convertFileSrc(clipPath, "clip")}
+          autoPlay
+          onEnded={handleEnded}
+          className="max-h-full max-w-full"
+        />
+      )}
+    </div>
+  );
+}
+
+export default HighlightWindow;
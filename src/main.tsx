@@ -1,13 +1,18 @@
 import React, { Suspense } from "react";
 import ReactDOM from "react-dom/client";
 import App from "./App";
+import HighlightWindow from "./components/HighlightWindow";
 
 import {getVersion} from "@tauri-apps/api/app";
+import {getCurrentWebviewWindow} from "@tauri-apps/api/webviewWindow";
+
+// 再生用ウィンドウはplay_highlights_windowで開かれる
+const isHighlightWindow = getCurrentWebviewWindow().label === "highlights";
 
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
     <Suspense>
-      <App versionPromise={getVersion()} />
+      {isHighlightWindow ? <HighlightWindow /> : <App versionPromise={getVersion()} />}
     </Suspense>
   </React.StrictMode>,
 );